@@ -1,8 +1,15 @@
-use core::{hash::Hash, iter, mem, ops::Index};
-use fnv::{FnvHashMap, FnvHashSet};
+use core::{
+    error::Error,
+    fmt::{self, Display, Write},
+    hash::{Hash, Hasher},
+    iter, mem,
+    ops::Index,
+};
+use fnv::{FnvHashMap, FnvHashSet, FnvHasher};
 use std::collections::hash_map::Entry;
 
-#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InputID(u32);
 
 impl InputID {
@@ -12,7 +19,8 @@ impl InputID {
     }
 }
 
-#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutputID(u32);
 
 impl OutputID {
@@ -22,24 +30,276 @@ impl OutputID {
     }
 }
 
-#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NodeID(u32);
 
+/// A tag describing the kind of data a port carries. Edges can only connect ports of the
+/// same kind. Ports are of the default kind, `PortKind(0)`, unless specified otherwise.
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PortKind(pub u32);
+
+/// A connection from a node's output port to a node's input port
+pub type Edge = ((NodeID, OutputID), (NodeID, InputID));
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CycleFound {
+    /// The nodes forming the cycle, in signal flow order, starting at the destination
+    /// of the rejected edge and ending at it's source.
+    pub path: Vec<NodeID>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EdgeInsertError {
+    /// Either one of the edge's ports doesn't exist.
+    PortNotFound,
+    /// The edge would have created a cycle.
+    CycleFound(CycleFound),
+    /// The edge's ports are of different [kinds](PortKind).
+    TypeMismatch,
+    /// The destination input already has as many connections as it's
+    /// [maximum fan-in](Input::max_fan_in) allows.
+    FanInExceeded,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PortError {
+    /// The edge's source node doesn't exist.
+    NodeNotFound(Edge),
+    /// The edge's source node exists, but has no such output.
+    OutputNotFound(Edge),
+}
+
+impl Display for InputID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Display for OutputID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Display for NodeID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Display for CycleFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("edge would create a cycle through nodes")?;
+
+        for (i, node_id) in self.path.iter().enumerate() {
+            let sep = if i == 0 { " " } else { " -> " };
+            write!(f, "{sep}{node_id}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for CycleFound {}
+
+impl Display for EdgeInsertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PortNotFound => {
+                f.write_str("the edge's source output or destination input doesn't exist")
+            }
+            Self::CycleFound(cycle) => cycle.fmt(f),
+            Self::TypeMismatch => f.write_str("the edge's ports are of different kinds"),
+            Self::FanInExceeded => {
+                f.write_str("the edge's destination input can't accept any more connections")
+            }
+        }
+    }
+}
+
+impl Error for EdgeInsertError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CycleFound(cycle) => Some(cycle),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (((from_node, output_id), (to_node, input_id)), reason) = match self {
+            Self::NodeNotFound(edge) => (edge, "source node doesn't exist"),
+            Self::OutputNotFound(edge) => (edge, "source node has no such output"),
+        };
+
+        write!(
+            f,
+            "edge from output {output_id} of node {from_node} \
+            to input {input_id} of node {to_node}: {reason}",
+        )
+    }
+}
+
+impl Error for PortError {}
+
+/// A node's port layout and settings, as found in a [`GraphSnapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeSnapshot {
+    pub id: NodeID,
+    pub latency: u64,
+    pub bypassed: bool,
+    /// Sorted in ascending order
+    pub input_ids: Vec<InputID>,
+    /// Sorted in ascending order
+    pub output_ids: Vec<OutputID>,
+}
+
+/// A plain, sorted copy of an [`AudioGraph`]'s topology, without metadata,
+/// see [`AudioGraph::snapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphSnapshot {
+    /// Sorted by ID
+    pub nodes: Vec<NodeSnapshot>,
+    /// Sorted in ascending order
+    pub edges: Vec<Edge>,
+}
+
+/// A reversible edit of an [`AudioGraph`], see [`AudioGraph::apply`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GraphCommand<M = ()> {
+    /// Insert `node`, at `id` or, if it's `None`, at the lowest free ID, tag it with
+    /// `metadata`, if any, then insert `edges`, which must all be connected to it.
+    InsertNode {
+        id: Option<NodeID>,
+        node: Node,
+        metadata: Option<M>,
+        edges: Vec<Edge>,
+    },
+    /// Remove a node, along with it's metadata and every edge connected to it.
+    RemoveNode(NodeID),
+    /// Remove `removed_edges`, then [replace](AudioGraph::replace_node) the node at `id` with
+    /// `node`, keeping it's metadata and the edges connected to ports they share, then insert
+    /// `edges`. Both lists of edges must only contain edges connected to the node.
+    ReplaceNode {
+        id: NodeID,
+        node: Node,
+        edges: Vec<Edge>,
+        removed_edges: Vec<Edge>,
+    },
+    InsertEdge(Edge),
+    RemoveEdge(Edge),
+    /// Tag a node with some metadata, or remove it's metadata if `None`.
+    SetMetadata(NodeID, Option<M>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommandError {
+    /// A node already exists at the requested ID.
+    NodeOccupied,
+    NodeNotFound,
+    /// The edge to insert already exists.
+    EdgeExists,
+    EdgeNotFound,
+    /// An edge to insert, or remove, along with a node isn't connected to it.
+    EdgeNotConnected,
+    EdgeInsertError(EdgeInsertError),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NodeOccupied => f.write_str("a node already exists at the requested ID"),
+            Self::NodeNotFound => f.write_str("no node exists at the requested ID"),
+            Self::EdgeExists => f.write_str("the edge already exists"),
+            Self::EdgeNotFound => f.write_str("the edge doesn't exist"),
+            Self::EdgeNotConnected => f.write_str("the edge isn't connected to the node"),
+            Self::EdgeInsertError(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for CommandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::EdgeInsertError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests;
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
-pub struct Input(FnvHashMap<NodeID, FnvHashSet<OutputID>>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Input {
+    connections: FnvHashMap<NodeID, FnvHashSet<OutputID>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    max_fan_in: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    kind: PortKind,
+    #[cfg_attr(feature = "serde", serde(default))]
+    required: bool,
+}
 
 impl Input {
     #[inline]
     pub fn connections(&self) -> &FnvHashMap<NodeID, FnvHashSet<OutputID>> {
-        &self.0
+        &self.connections
+    }
+
+    /// Returns the number of outputs connected to this input.
+    #[inline]
+    pub fn fan_in(&self) -> usize {
+        self.connections.values().map(FnvHashSet::len).sum()
+    }
+
+    /// The maximum number of outputs that can be connected to this input, `None` means no limit.
+    #[inline]
+    pub fn max_fan_in(&self) -> Option<usize> {
+        self.max_fan_in
+    }
+
+    /// Sets the maximum number of outputs that can be connected to this input, `None`
+    /// meaning no limit. Edges already connected to it are kept, even if they exceed the limit.
+    #[inline]
+    pub fn set_max_fan_in(&mut self, max_fan_in: Option<usize>) {
+        self.max_fan_in = max_fan_in;
+    }
+
+    #[inline]
+    pub fn kind(&self) -> PortKind {
+        self.kind
+    }
+
+    /// Sets the kind of this input. Edges already connected to it are kept,
+    /// even if they come from outputs of another kind.
+    #[inline]
+    pub fn set_kind(&mut self, kind: PortKind) {
+        self.kind = kind;
+    }
+
+    /// Whether this input must be connected for it's node to work properly,
+    /// see [`AudioGraph::unsatisfied_inputs`].
+    #[inline]
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    #[inline]
+    pub fn set_required(&mut self, required: bool) {
+        self.required = required;
     }
 
     #[inline]
     fn insert_output(&mut self, (node_index, port_index): (NodeID, OutputID)) -> bool {
-        match self.0.entry(node_index) {
+        match self.connections.entry(node_index) {
             Entry::Occupied(e) => e.into_mut().insert(port_index),
             Entry::Vacant(e) => {
                 e.insert(FnvHashSet::from_iter([port_index]));
@@ -52,24 +312,33 @@ impl Input {
     pub fn remove_port(&mut self, (node_index, port_index): (&NodeID, &OutputID)) -> bool {
         let mut empty = false;
 
-        let tmp = self.0.get_mut(node_index).is_some_and(|ports| {
+        let tmp = self.connections.get_mut(node_index).is_some_and(|ports| {
             let tmp = ports.remove(port_index);
             empty = ports.is_empty();
             tmp
         });
 
         if empty {
-            self.0.remove(node_index);
+            self.connections.remove(node_index);
         }
 
         tmp
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub latency: u64,
+    /// When set, the node isn't processed. Instead, each of it's outputs forwards the input
+    /// with the same rank (in ascending ID order), or silence if there is no such input,
+    /// or it isn't connected. Bypassed nodes have no latency.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bypassed: bool,
     output_ids: FnvHashSet<OutputID>,
+    /// Only the outputs that aren't of the default kind are stored here
+    #[cfg_attr(feature = "serde", serde(default))]
+    output_kinds: FnvHashMap<OutputID, PortKind>,
     inputs: FnvHashMap<InputID, Input>,
 }
 
@@ -77,11 +346,15 @@ impl Node {
     fn with_reversed_io_layout(&self) -> Self {
         let Self {
             latency,
+            bypassed,
             output_ids,
             inputs,
+            ..
         } = self;
         Self {
             latency: *latency,
+            bypassed: *bypassed,
+            output_kinds: FnvHashMap::default(),
             output_ids: inputs.keys().cloned().map(InputID::transpose).collect(),
             inputs: output_ids
                 .iter()
@@ -92,6 +365,16 @@ impl Node {
         }
     }
 
+    fn without_connections(&self) -> Self {
+        let mut node = self.clone();
+
+        for input in node.inputs.values_mut() {
+            input.connections.clear();
+        }
+
+        node
+    }
+
     #[inline]
     pub fn get_input_mut(&mut self, id: &InputID) -> Option<&mut Input> {
         self.inputs.get_mut(id)
@@ -131,9 +414,44 @@ impl Node {
 
         panic!("Index overflow")
     }
+
+    /// Removes the output `id`, returns whether it existed.
+    ///
+    /// Edges are stored in their destination inputs, so, for a node in a graph, this leaves
+    /// the edges coming from it dangling. Use [`AudioGraph::remove_output`] instead.
+    #[inline]
+    pub fn remove_output(&mut self, id: &OutputID) -> bool {
+        self.output_kinds.remove(id);
+        self.output_ids.remove(id)
+    }
 }
 
 impl Node {
+    /// Returns the kind of the output `id`, or `None` if there is no such output.
+    #[inline]
+    pub fn output_kind(&self, id: &OutputID) -> Option<PortKind> {
+        self.output_ids
+            .contains(id)
+            .then(|| self.output_kinds.get(id).copied().unwrap_or_default())
+    }
+
+    /// Sets the kind of the output `id`, returns whether there is such an output. Edges
+    /// already connected to it are kept, even if they lead to inputs of another kind.
+    #[inline]
+    pub fn set_output_kind(&mut self, id: &OutputID, kind: PortKind) -> bool {
+        if !self.output_ids.contains(id) {
+            return false;
+        }
+
+        if kind == PortKind::default() {
+            self.output_kinds.remove(id);
+        } else {
+            self.output_kinds.insert(id.clone(), kind);
+        }
+
+        true
+    }
+
     #[inline]
     pub fn inputs(&self) -> &FnvHashMap<InputID, Input> {
         &self.inputs
@@ -156,11 +474,17 @@ impl BufferAllocator {
         self.ports.len()
     }
 
-    fn get_free(&mut self) -> usize {
-        fn get_or_insert_empty_set_index<T>(list: &mut Vec<FnvHashSet<T>>) -> usize {
+    /// Returns the index of a buffer with no claims, that isn't in `excluded`
+    fn get_free(&mut self, excluded: &[usize]) -> usize {
+        fn get_or_insert_empty_set_index<T>(
+            list: &mut Vec<FnvHashSet<T>>,
+            excluded: &[usize],
+        ) -> usize {
             list.iter()
                 .enumerate()
-                .find_map(|(i, port_idxs)| port_idxs.is_empty().then_some(i))
+                .find_map(|(i, port_idxs)| {
+                    (port_idxs.is_empty() && !excluded.contains(&i)).then_some(i)
+                })
                 .unwrap_or_else(|| {
                     let tmp = list.len();
                     list.push(FnvHashSet::default());
@@ -168,7 +492,7 @@ impl BufferAllocator {
                 })
         }
 
-        get_or_insert_empty_set_index(&mut self.ports)
+        get_or_insert_empty_set_index(&mut self.ports, excluded)
     }
 
     fn claim(
@@ -198,8 +522,8 @@ impl BufferAllocator {
         new
     }
 
-    fn remove_claim(&mut self, port: &(NodeID, InputID)) -> usize {
-        let i = self.buffers.remove(port).unwrap();
+    fn remove_claim(&mut self, port: &(NodeID, InputID)) -> Option<usize> {
+        let i = self.buffers.remove(port)?;
 
         assert!(
             self.ports
@@ -209,22 +533,44 @@ impl BufferAllocator {
             "INTERNAL ERROR: port reserves a buffer but is not in it's port list entry"
         );
 
-        i
+        Some(i)
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Task {
     Node {
         id: NodeID,
+        /// The buffers to read each connected input from. Unconnected inputs are left out.
         inputs: FnvHashMap<InputID, usize>,
+        /// The buffers to write each connected output to. Unconnected outputs are left out.
         outputs: FnvHashMap<OutputID, usize>,
     },
+    /// Writes the sum of `left` and `right` into `output`, which may be either one of them.
+    ///
+    /// Inputs connected to several outputs read the sum of all their signals, built up by a
+    /// chain of these, each adding one more signal to the previous sum, so that no
+    /// buffer ever needs to be cleared beforehand.
     Sum {
         left: usize,
         right: usize,
         output: usize,
     },
+    /// Delays the signal going through `edge` by `delay` samples, so that it reaches it's
+    /// destination aligned with the node's other, higher latency, inputs.
+    ///
+    /// Each such edge needs it's own delay line, kept across runs of the schedule.
+    Delay {
+        edge: Edge,
+        input: usize,
+        output: usize,
+        delay: u64,
+    },
+    /// Copies `input` into `output`, in place of a bypassed node.
+    Copy { input: usize, output: usize },
+    /// Fills `output` with silence, in place of a bypassed node.
+    Clear { output: usize },
 }
 
 impl Task {
@@ -249,6 +595,45 @@ impl Task {
             output,
         }
     }
+
+    #[inline]
+    pub fn delay(edge: Edge, input: usize, output: usize, delay: u64) -> Self {
+        Self::Delay {
+            edge,
+            input,
+            output,
+            delay,
+        }
+    }
+
+    #[inline]
+    pub fn copy(input: usize, output: usize) -> Self {
+        Self::Copy { input, output }
+    }
+
+    #[inline]
+    pub fn clear(output: usize) -> Self {
+        Self::Clear { output }
+    }
+
+    /// Returns whether `input` and `output` were assigned the same buffer, in which case
+    /// the node must be processed in place.
+    ///
+    /// Always `false` for tasks other than [`Task::Node`], and for ports the task doesn't
+    /// assign a buffer to.
+    #[inline]
+    pub fn aliases(&self, input: &InputID, output: &OutputID) -> bool {
+        let Self::Node {
+            inputs, outputs, ..
+        } = self
+        else {
+            return false;
+        };
+
+        inputs
+            .get(input)
+            .is_some_and(|buf_index| outputs.get(output) == Some(buf_index))
+    }
 }
 
 #[derive(Debug)]
@@ -258,69 +643,183 @@ struct Scheduler {
 }
 
 impl Scheduler {
+    /// Claims `buf_index` for `ports`, scheduling a sum for each port that has
+    /// already claimed another buffer. Sums never output to a buffer in `reserved`.
+    fn claim_or_sum(
+        allocator: &mut BufferAllocator,
+        schedule: &mut Vec<Task>,
+        buf_index: usize,
+        ports: FnvHashSet<(NodeID, InputID)>,
+        reserved: &[usize],
+    ) {
+        for port_idx in allocator.claim(buf_index, ports) {
+            let other_buf_idx = allocator
+                .remove_claim(&port_idx)
+                .expect("INTERNAL ERROR: redundant claim found on unclaimed port");
+            let new_free_buf = allocator.get_free(reserved);
+            assert!(
+                allocator
+                    .claim(new_free_buf, FnvHashSet::from_iter([port_idx]))
+                    .is_empty(),
+                "INTERNAL ERROR: redundant claims cleared yet still found"
+            );
+
+            schedule.push(Task::Sum {
+                left: buf_index,
+                right: other_buf_idx,
+                output: new_free_buf,
+            });
+        }
+    }
+
     fn compile(self) -> (usize, Vec<Task>) {
         let mut allocator = BufferAllocator::default();
         let mut schedule = vec![];
 
         let Self {
-            mut transposed,
+            transposed,
             process_order,
         } = self;
 
-        for node_id in process_order {
-            let node = transposed.get_node_mut(&node_id).unwrap();
+        // the latency the signal accumulates, along it's highest latency path, before
+        // reaching each node's inputs. All of a node's inputs are delayed to match it
+        let mut input_latencies = FnvHashMap::<&NodeID, u64>::default();
+
+        let output_latency = |input_latencies: &FnvHashMap<_, u64>, node_id, node: &Node| {
+            let latency = if node.bypassed { 0 } else { node.latency };
+            input_latencies.get(node_id).copied().unwrap_or(0) + latency
+        };
+
+        for node_id in &process_order {
+            let node = &transposed[node_id];
+            let output_latency = output_latency(&input_latencies, node_id, node);
+
+            for port in node.inputs().values() {
+                for consumer in port.connections().keys() {
+                    let latency = input_latencies.entry(consumer).or_default();
+                    *latency = output_latency.max(*latency);
+                }
+            }
+        }
+
+        for node_id in &process_order {
+            let node = &transposed[node_id];
+            let output_latency = output_latency(&input_latencies, node_id, node);
 
-            #[allow(clippy::clone_on_copy)]
-            let inputs = node
+            // inputs that aren't connected to anything never get a buffer claimed for them
+            let inputs: FnvHashMap<_, _> = node
                 .output_ids()
                 .iter()
                 .cloned()
                 .map(OutputID::transpose)
-                .map(|id| (id.clone(), allocator.remove_claim(&(node_id.clone(), id))))
+                .filter_map(|id| {
+                    allocator
+                        .remove_claim(&(node_id.clone(), id.clone()))
+                        .map(|buf_index| (id, buf_index))
+                })
                 .collect();
 
-            let outputs = node
+            let output_ports: Vec<_> = node
                 .inputs()
                 .iter()
                 .filter(|(_, port)| !port.connections().is_empty())
-                .map(|(id, _)| id.clone().transpose())
-                .zip(iter::repeat_with(|| allocator.get_free()))
                 .collect();
 
-            schedule.push(Task::Node {
-                id: node_id,
-                inputs,
-                outputs,
-            });
-
-            let Some(Task::Node { outputs, .. }) = schedule.last() else {
-                panic!()
+            // the copies replacing a bypassed node run one after the other, so
+            // their outputs musn't overwrite inputs that are yet to be copied
+            let mut reserved: Vec<_> = if node.bypassed {
+                inputs.values().copied().collect()
+            } else {
+                vec![]
             };
 
-            for (buf_index, port) in outputs.clone().into_values().zip(node.inputs.values()) {
-                for port_idx in allocator.claim(
-                    buf_index,
-                    port.connections()
-                        .iter()
-                        .flat_map(|(node, ports)| {
-                            ports.iter().map(|p| (node.clone(), p.clone().transpose()))
-                        })
-                        .collect(),
-                ) {
-                    let other_buf_idx = allocator.remove_claim(&port_idx);
-                    let new_free_buf = allocator.get_free();
-                    assert!(
-                        allocator
-                            .claim(new_free_buf, FnvHashSet::from_iter([port_idx]))
-                            .is_empty(),
-                        "INTERNAL ERROR: redundant claims cleared yet still found"
-                    );
+            // every output must be given it's own buffer, even if it ends up not claiming it,
+            // and they may all still be needed by the sums and delays that follow
+            let mut output_buffers = vec![];
+            for _ in &output_ports {
+                let buf_index = allocator.get_free(&reserved);
+                reserved.push(buf_index);
+                output_buffers.push(buf_index);
+            }
+
+            let outputs: FnvHashMap<_, _> = output_ports
+                .iter()
+                .map(|(id, _)| (*id).clone().transpose())
+                .zip(output_buffers.iter().copied())
+                .collect();
+
+            if node.bypassed {
+                let mut input_ids: Vec<_> = node.output_ids().iter().collect();
+                input_ids.sort_unstable();
+
+                let mut output_ids: Vec<_> = node.inputs().keys().collect();
+                output_ids.sort_unstable();
+
+                for (rank, output_id) in output_ids.into_iter().enumerate() {
+                    let Some(&output) = outputs.get(&output_id.clone().transpose()) else {
+                        continue;
+                    };
+
+                    let input = input_ids
+                        .get(rank)
+                        .and_then(|&id| inputs.get(&id.clone().transpose()));
+
+                    schedule.push(match input {
+                        Some(&input) => Task::Copy { input, output },
+                        None => Task::Clear { output },
+                    });
+                }
+            } else {
+                schedule.push(Task::Node {
+                    id: node_id.clone(),
+                    inputs,
+                    outputs,
+                });
+            }
+
+            for (&buf_index, (id, port)) in output_buffers.iter().zip(output_ports) {
+                let mut aligned_ports = FnvHashSet::default();
+                let mut delayed_ports = vec![];
 
-                    schedule.push(Task::Sum {
-                        left: buf_index,
-                        right: other_buf_idx,
-                        output: new_free_buf,
+                for (consumer, ports) in port.connections() {
+                    let delay = input_latencies[consumer] - output_latency;
+
+                    for port_id in ports {
+                        let port_idx = (consumer.clone(), port_id.clone().transpose());
+
+                        if delay == 0 {
+                            aligned_ports.insert(port_idx);
+                        } else {
+                            delayed_ports.push((port_idx, delay));
+                        }
+                    }
+                }
+
+                Self::claim_or_sum(
+                    &mut allocator,
+                    &mut schedule,
+                    buf_index,
+                    aligned_ports,
+                    &output_buffers,
+                );
+
+                for (port_idx, delay) in delayed_ports {
+                    let delay_buf = allocator.get_free(&output_buffers);
+
+                    schedule.push(Task::Delay {
+                        edge: ((node_id.clone(), id.clone().transpose()), port_idx.clone()),
+                        input: buf_index,
+                        output: delay_buf,
+                        delay,
                     });
+
+                    Self::claim_or_sum(
+                        &mut allocator,
+                        &mut schedule,
+                        delay_buf,
+                        FnvHashSet::from_iter([port_idx]),
+                        &output_buffers,
+                    );
                 }
             }
         }
@@ -329,77 +828,199 @@ impl Scheduler {
     }
 }
 
-#[derive(Clone, Debug, Default)]
-pub struct AudioGraph {
+/// A directed acyclic graph of audio nodes.
+///
+/// Each node can optionally be tagged with some user metadata, of type `M`, kept
+/// separately from the graph's structure.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+// serde would otherwise require `M: Default`, because of the defaulted fields
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "M: serde::Deserialize<'de>"))
+)]
+pub struct AudioGraph<M = ()> {
     nodes: FnvHashMap<NodeID, Node>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    metadata: FnvHashMap<NodeID, M>,
+    /// The sequence number of each node, increasing with every insertion
+    #[cfg_attr(feature = "serde", serde(default))]
+    insertion_order: FnvHashMap<NodeID, u64>,
+    /// The sequence number of the next inserted node
+    #[cfg_attr(feature = "serde", serde(default))]
+    next_sequence: u64,
+}
+
+// The insertion order is only kept to list nodes, it isn't part of the graph's contents,
+// and neither is the next sequence number
+impl<M: PartialEq> PartialEq for AudioGraph<M> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.nodes == other.nodes && self.metadata == other.metadata
+    }
+}
+
+impl<M: Eq> Eq for AudioGraph<M> {}
+
+/// A graph, tagged with the version of it's serialized format, for persisting graphs
+/// across versions of this crate.
+///
+/// Graphs saved in older formats are loaded into their own variant, then brought up to date
+/// with [`migrate`](Self::migrate). Graphs saved in unknown, newer, formats fail to load.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "version", content = "graph")]
+pub enum VersionedGraph<M = ()> {
+    #[serde(rename = "1")]
+    V1(AudioGraph<M>),
+}
+
+#[cfg(feature = "serde")]
+impl<M> VersionedGraph<M> {
+    /// Converts the graph from it's saved format to the current one.
+    #[inline]
+    pub fn migrate(self) -> AudioGraph<M> {
+        match self {
+            Self::V1(graph) => graph,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<M> From<AudioGraph<M>> for VersionedGraph<M> {
+    /// Tags `graph` with the current format version.
+    #[inline]
+    fn from(graph: AudioGraph<M>) -> Self {
+        Self::V1(graph)
+    }
+}
+
+// Not generic over `M` so that `AudioGraph::default()` doesn't need type annotations.
+// Use `AudioGraph::new()` for other metadata types.
+impl Default for AudioGraph {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl Index<&NodeID> for AudioGraph {
+impl<M> Index<&NodeID> for AudioGraph<M> {
     type Output = Node;
+
+    /// # Panics
+    ///
+    /// if no node exists at `key`, see [`AudioGraph::get_node`] for a non-panicking lookup
     #[inline]
     fn index(&self, key: &NodeID) -> &Self::Output {
         self.get_node(key).expect("no node found for this id")
     }
 }
 
-impl AudioGraph {
+impl<M> AudioGraph<M> {
     #[inline]
-    fn fill_inputs(&mut self, transposed: &Self, node_index: &NodeID, processed: &mut Vec<NodeID>) {
+    pub fn new() -> Self {
+        Self {
+            nodes: FnvHashMap::default(),
+            metadata: FnvHashMap::default(),
+            insertion_order: FnvHashMap::default(),
+            next_sequence: 0,
+        }
+    }
+
+    /// Inserts the transposed edges feeding the node at `node_index`, in `transposed`,
+    /// and (transitively) the nodes they start at, pushing each node to `processed` after
+    /// every node feeding it.
+    ///
+    /// # Panics
+    ///
+    /// if the nodes feeding `node_index` form a cycle
+    fn fill_inputs<N>(
+        &mut self,
+        transposed: &AudioGraph<N>,
+        node_index: &NodeID,
+        processed: &mut Vec<NodeID>,
+    ) {
+        fn feeding_edges<'a, N>(
+            graph: &'a AudioGraph<N>,
+            node_index: &NodeID,
+        ) -> impl Iterator<Item = (&'a InputID, &'a NodeID, &'a FnvHashSet<OutputID>)> {
+            let node = graph.get_node(node_index).unwrap();
+
+            node.inputs().iter().flat_map(|(id, input)| {
+                input
+                    .connections()
+                    .iter()
+                    .map(move |(node_idx, port_indices)| (id, node_idx, port_indices))
+            })
+        }
+
         if processed.contains(node_index) {
             return;
         }
 
-        let this_node = transposed.get_node(node_index).unwrap();
+        // each node on the stack is fed by the one after it
+        let mut stack = vec![(node_index, feeding_edges(transposed, node_index).peekable())];
+        let mut on_stack = FnvHashSet::from_iter([node_index]);
+
+        while let Some((node_index, edges)) = stack.last_mut() {
+            let node_index = *node_index;
+
+            let Some(&(id, node_idx, port_indices)) = edges.peek() else {
+                on_stack.remove(node_index);
+                processed.push(node_index.clone());
+                stack.pop();
+                continue;
+            };
+
+            // process the node the edge starts at first, then come back to the edge
+            if !processed.contains(node_idx) {
+                assert!(on_stack.insert(node_idx), "graph contains a cycle");
+                stack.push((node_idx, feeding_edges(transposed, node_idx).peekable()));
+                continue;
+            }
 
-        for (id, input) in this_node.inputs().iter() {
             let output_id = id.clone().transpose();
 
-            for (node_idx, port_indices) in input.connections().iter() {
-                self.fill_inputs(transposed, node_idx, processed);
-
-                for input_id in port_indices.iter().cloned().map(OutputID::transpose) {
-                    let node = if let Some(node) = self.get_node_mut(node_idx) {
-                        node
-                    } else {
-                        let Ok(node) = self.try_insert_node(
-                            node_idx.clone(),
-                            transposed
-                                .get_node(node_idx)
-                                .unwrap()
-                                .with_reversed_io_layout(),
-                        ) else {
-                            panic!("inconsistent Hash and Eq implementations for NodeID?");
-                        };
-
-                        node
+            for input_id in port_indices.iter().cloned().map(OutputID::transpose) {
+                let node = if let Some(node) = self.get_node_mut(node_idx) {
+                    node
+                } else {
+                    let Ok(node) = self.try_insert_node(
+                        node_idx.clone(),
+                        transposed
+                            .get_node(node_idx)
+                            .unwrap()
+                            .with_reversed_io_layout(),
+                    ) else {
+                        panic!("inconsistent Hash and Eq implementations for NodeID?");
                     };
 
-                    let new = node
-                        .get_input_mut(&input_id)
-                        .unwrap()
-                        .insert_output((node_index.clone(), output_id.clone()));
+                    node
+                };
 
-                    assert!(new, "INTERNAL ERRROR: port must be newly inserted");
-                }
+                let new = node
+                    .get_input_mut(&input_id)
+                    .unwrap()
+                    .insert_output((node_index.clone(), output_id.clone()));
+
+                assert!(new, "INTERNAL ERRROR: port must be newly inserted");
             }
-        }
 
-        processed.push(node_index.clone());
+            edges.next();
+        }
     }
 
     #[inline]
-    fn scheduler(&self, root_nodes: FnvHashSet<NodeID>) -> Scheduler {
-        let mut transposed = Self::default();
+    fn scheduler(&self, root_nodes: impl IntoIterator<Item = NodeID>) -> Scheduler {
+        let mut transposed = AudioGraph::default();
 
         let mut process_order = vec![];
 
         for node_idx in root_nodes {
-            assert!(transposed
-                .try_insert_node(
-                    node_idx.clone(),
-                    self.get_node(&node_idx).unwrap().with_reversed_io_layout()
-                )
-                .is_ok());
+            // root nodes may have already been reached as another root's dependency
+            if let Entry::Vacant(e) = transposed.nodes.entry(node_idx.clone()) {
+                e.insert(self.get_node(&node_idx).unwrap().with_reversed_io_layout());
+            }
             transposed.fill_inputs(self, &node_idx, &mut process_order);
         }
 
@@ -409,19 +1030,60 @@ impl AudioGraph {
         }
     }
 
+    /// Returns every node of the graph, ordered such that each node comes after all the
+    /// nodes feeding it.
+    ///
+    /// The order is deterministic for a given graph. Isolated nodes (with no connections)
+    /// appear, like every other node, exactly once.
+    ///
+    /// Returns `None` if the graph contains a cycle, see [`find_any_cycle`](Self::find_any_cycle).
+    #[inline]
+    pub fn topological_order(&self) -> Option<Vec<NodeID>> {
+        if !self.is_acyclic() {
+            return None;
+        }
+
+        let mut node_ids: Vec<_> = self.nodes.keys().cloned().collect();
+        node_ids.sort_unstable();
+
+        Some(self.scheduler(node_ids).process_order)
+    }
+
+    /// Computes a schedule processing `root_nodes`, returning it along with the number of
+    /// buffers it needs.
+    ///
+    /// Only the root nodes and the nodes they (transitively) depend on are scheduled,
+    /// any other node is skipped entirely.
+    ///
+    /// The schedule, including the order in which inputs are summed, is deterministic:
+    /// graphs built by the same sequence of edits, or cloned from one another, always
+    /// compile to the same schedule. Structurally equal graphs built in different orders
+    /// may, however, be scheduled differently.
     #[inline]
     pub fn compile(&self, root_nodes: impl IntoIterator<Item = NodeID>) -> (usize, Vec<Task>) {
         self.scheduler(FnvHashSet::from_iter(root_nodes)).compile()
     }
 }
 
-impl AudioGraph {
+impl<M> AudioGraph<M> {
     #[inline]
-    pub fn try_insert_edge(
+    pub fn insert_edge(
+        &mut self,
+        from: (NodeID, OutputID),
+        to: (NodeID, InputID),
+    ) -> Result<bool, EdgeInsertError> {
+        self.connect(from, to, true)
+    }
+
+    /// Like [`insert_edge`](Self::insert_edge), but only checks the port rules (kinds and
+    /// maximum fan-in) if `check_rules` is set. Restoring edges that were valid when they were first inserted
+    /// must not check them, as they may have changed since.
+    fn connect(
         &mut self,
         from: (NodeID, OutputID),
         to: (NodeID, InputID),
-    ) -> Result<bool, bool> {
+        check_rules: bool,
+    ) -> Result<bool, EdgeInsertError> {
         // If either of the ports don't exist, error out
         if self
             .get_node(&to.0)
@@ -431,11 +1093,29 @@ impl AudioGraph {
                 .get_node(&from.0)
                 .is_none_or(|node| !node.output_ids().contains(&from.1))
         {
-            return Err(false);
+            return Err(EdgeInsertError::PortNotFound);
+        }
+
+        let input = &self[&to.0].inputs()[&to.1];
+
+        if check_rules && self[&from.0].output_kind(&from.1) != Some(input.kind()) {
+            return Err(EdgeInsertError::TypeMismatch);
+        }
+
+        if input
+            .connections()
+            .get(&from.0)
+            .is_some_and(|output_ids| output_ids.contains(&from.1))
+        {
+            return Ok(false);
+        }
+
+        if check_rules && input.max_fan_in().is_some_and(|max| input.fan_in() >= max) {
+            return Err(EdgeInsertError::FanInExceeded);
         }
 
-        if self.is_connected(&from.0, &to.0) {
-            return Err(true);
+        if let Some(path) = self.find_path(&from.0, &to.0) {
+            return Err(EdgeInsertError::CycleFound(CycleFound { path }));
         }
 
         Ok(self
@@ -446,55 +1126,1350 @@ impl AudioGraph {
             .insert_output(from))
     }
 
-    /// # Panics
+    /// Inserts every edge in `edges`, in order, stopping at the first one that can't be inserted.
     ///
-    /// if no node exists at either `from` or `to`
-    fn is_connected(&self, from: &NodeID, to: &NodeID) -> bool {
-        if from == to {
-            return true;
-        }
+    /// On failure, all edges inserted by this call are removed, leaving the graph untouched.
+    pub fn insert_edges(&mut self, edges: &[Edge]) -> Result<(), EdgeInsertError> {
+        self.connect_all(edges, true).map(drop)
+    }
 
-        for port in self.get_node(from).unwrap().inputs().values() {
-            for node in port.connections().keys() {
-                if self.is_connected(node, to) {
-                    return true;
+    /// Like [`insert_edges`](Self::insert_edges), see [`connect`](Self::connect), but returns
+    /// the edges that weren't already in the graph.
+    fn connect_all(
+        &mut self,
+        edges: &[Edge],
+        check_rules: bool,
+    ) -> Result<Vec<Edge>, EdgeInsertError> {
+        let mut inserted = vec![];
+
+        for (from, to) in edges {
+            match self.connect(from.clone(), to.clone(), check_rules) {
+                Ok(true) => inserted.push((from.clone(), to.clone())),
+                Ok(false) => (),
+                Err(e) => {
+                    for ((from_node, output_id), (to_node, input_id)) in inserted {
+                        self.remove_edge((&from_node, &output_id), (&to_node, &input_id));
+                    }
+
+                    return Err(e);
                 }
             }
         }
 
-        false
+        Ok(inserted)
     }
 
+    /// Removes the edge from `from` to `to`, returns whether it existed.
     #[inline]
-    pub fn get_node(&self, index: &NodeID) -> Option<&Node> {
-        self.nodes.get(index)
+    pub fn remove_edge(&mut self, from: (&NodeID, &OutputID), to: (&NodeID, &InputID)) -> bool {
+        self.get_node_mut(to.0)
+            .and_then(|node| node.get_input_mut(to.1))
+            .is_some_and(|input| input.remove_port(from))
     }
 
-    #[inline]
-    pub fn get_node_mut(&mut self, index: &NodeID) -> Option<&mut Node> {
-        self.nodes.get_mut(index)
+    /// Moves the edge from `from` to `old_to`, so that it ends at `new_to` instead.
+    ///
+    /// The new edge is validated like in [`insert_edge`](Self::insert_edge) before the old
+    /// one is removed, so that, on failure, the graph is left unchanged. Returns whether
+    /// the edge to move existed, nothing is done if it doesn't.
+    pub fn move_edge(
+        &mut self,
+        from: (NodeID, OutputID),
+        old_to: (NodeID, InputID),
+        new_to: (NodeID, InputID),
+    ) -> Result<bool, EdgeInsertError> {
+        let exists = self
+            .get_node(&old_to.0)
+            .and_then(|node| node.inputs().get(&old_to.1))
+            .and_then(|input| input.connections().get(&from.0))
+            .is_some_and(|output_ids| output_ids.contains(&from.1));
+
+        if !exists || old_to == new_to {
+            return Ok(exists);
+        }
+
+        self.insert_edge(from.clone(), new_to)?;
+        self.remove_edge((&from.0, &from.1), (&old_to.0, &old_to.1));
+
+        Ok(true)
     }
 
+    /// Applies `command` to the graph. Edges are inserted like with
+    /// [`insert_edge`](Self::insert_edge).
+    ///
+    /// On success, returns the command undoing it, to be [restored](Self::restore), so that
+    /// undo/redo histories can be built by stacking the returned commands. On failure, the
+    /// graph is left unchanged.
     #[inline]
-    fn try_insert_node(&mut self, id: NodeID, node: Node) -> Result<&mut Node, (&mut Node, Node)> {
-        match self.nodes.entry(id) {
-            Entry::Occupied(e) => Err((e.into_mut(), node)),
-            Entry::Vacant(e) => Ok(e.insert(node)),
-        }
+    pub fn apply(&mut self, command: GraphCommand<M>) -> Result<GraphCommand<M>, CommandError> {
+        self.run(command, true)
     }
 
+    /// Like [`apply`](Self::apply), but inserted edges are only checked for missing ports and
+    /// cycles, not against their ports' kinds or maximum fan-in.
+    ///
+    /// Meant for commands returned by [`apply`](Self::apply), [`restore`](Self::restore) and
+    /// [`diff`](Self::diff), whose edges were valid when first connected, but may no longer
+    /// be if their ports' rules have changed since.
     #[inline]
-    pub fn insert_node(&mut self, node: Node) -> NodeID {
+    pub fn restore(&mut self, command: GraphCommand<M>) -> Result<GraphCommand<M>, CommandError> {
+        self.run(command, false)
+    }
+
+    /// See [`apply`](Self::apply) and [`restore`](Self::restore).
+    fn run(
+        &mut self,
+        command: GraphCommand<M>,
+        check_rules: bool,
+    ) -> Result<GraphCommand<M>, CommandError> {
+        let is_connected_to = |((from, _), (to, _)): &Edge, id: &NodeID| from == id || to == id;
+
+        match command {
+            GraphCommand::InsertNode {
+                id,
+                node,
+                metadata,
+                edges,
+            } => {
+                let id = match id {
+                    Some(id) => {
+                        self.try_insert_node(id.clone(), node)
+                            .map_err(|_| CommandError::NodeOccupied)?;
+                        self.record_insertion(&id);
+                        id
+                    }
+                    None => self.insert_node(node),
+                };
+
+                if !edges.iter().all(|edge| is_connected_to(edge, &id)) {
+                    self.remove_node(&id);
+                    return Err(CommandError::EdgeNotConnected);
+                }
+
+                if let Err(e) = self.connect_all(&edges, check_rules) {
+                    self.remove_node(&id);
+                    return Err(CommandError::EdgeInsertError(e));
+                }
+
+                if let Some(metadata) = metadata {
+                    self.metadata.insert(id.clone(), metadata);
+                }
+
+                Ok(GraphCommand::RemoveNode(id))
+            }
+            GraphCommand::RemoveNode(id) => {
+                let metadata = self.metadata.remove(&id);
+                let (node, edges) = self.remove_node(&id).ok_or(CommandError::NodeNotFound)?;
+
+                Ok(GraphCommand::InsertNode {
+                    id: Some(id),
+                    node,
+                    metadata,
+                    edges,
+                })
+            }
+            GraphCommand::ReplaceNode {
+                id,
+                node,
+                edges,
+                removed_edges,
+            } => {
+                if self.get_node(&id).is_none() {
+                    return Err(CommandError::NodeNotFound);
+                }
+
+                if !edges
+                    .iter()
+                    .chain(&removed_edges)
+                    .all(|edge| is_connected_to(edge, &id))
+                {
+                    return Err(CommandError::EdgeNotConnected);
+                }
+
+                for (i, ((from, output_id), (to, input_id))) in removed_edges.iter().enumerate() {
+                    if !self.remove_edge((from, output_id), (to, input_id)) {
+                        self.connect_all(&removed_edges[..i], false)
+                            .expect("INTERNAL ERROR: couldn't restore removed edges");
+
+                        return Err(CommandError::EdgeNotFound);
+                    }
+                }
+
+                // edges connected to ports missing from `node`, dropped by the replacement
+                let mut dropped: Vec<_> = self
+                    .edges()
+                    .filter(|((from, output_id), (to, input_id))| {
+                        (from == &id && !node.output_ids().contains(output_id))
+                            || (to == &id && !node.inputs().contains_key(input_id))
+                    })
+                    .collect();
+
+                let Ok(old) = self.replace_node(&id, node) else {
+                    unreachable!()
+                };
+
+                let inserted = match self.connect_all(&edges, check_rules) {
+                    Ok(inserted) => inserted,
+                    Err(e) => {
+                        let _ = self.replace_node(&id, old);
+
+                        dropped.extend(removed_edges);
+                        self.connect_all(&dropped, false)
+                            .expect("INTERNAL ERROR: couldn't restore the replaced node's edges");
+
+                        return Err(CommandError::EdgeInsertError(e));
+                    }
+                };
+
+                dropped.extend(removed_edges);
+
+                Ok(GraphCommand::ReplaceNode {
+                    id,
+                    node: old,
+                    edges: dropped,
+                    removed_edges: inserted,
+                })
+            }
+            GraphCommand::InsertEdge((from, to)) => {
+                match self.connect(from.clone(), to.clone(), check_rules) {
+                    Ok(true) => Ok(GraphCommand::RemoveEdge((from, to))),
+                    Ok(false) => Err(CommandError::EdgeExists),
+                    Err(e) => Err(CommandError::EdgeInsertError(e)),
+                }
+            }
+            GraphCommand::RemoveEdge((from, to)) => {
+                if self.remove_edge((&from.0, &from.1), (&to.0, &to.1)) {
+                    Ok(GraphCommand::InsertEdge((from, to)))
+                } else {
+                    Err(CommandError::EdgeNotFound)
+                }
+            }
+            GraphCommand::SetMetadata(id, metadata) => {
+                if self.get_node(&id).is_none() {
+                    return Err(CommandError::NodeNotFound);
+                }
+
+                let old = match metadata {
+                    Some(metadata) => self.metadata.insert(id.clone(), metadata),
+                    None => self.metadata.remove(&id),
+                };
+
+                Ok(GraphCommand::SetMetadata(id, old))
+            }
+        }
+    }
+
+    /// Same as [`insert_edge`](Self::insert_edge).
+    #[inline]
+    pub fn try_insert_edge(
+        &mut self,
+        from: (NodeID, OutputID),
+        to: (NodeID, InputID),
+    ) -> Result<bool, EdgeInsertError> {
+        self.insert_edge(from, to)
+    }
+
+    /// Returns the IDs of the nodes that neither are one of `roots`, nor feed one of them, even
+    /// indirectly, sorted. These are the nodes [`compile`](Self::compile) skips for these roots,
+    /// including the nodes that only feed other such nodes.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at one of `roots`
+    pub fn unreachable_from(&self, roots: &[NodeID]) -> Vec<NodeID> {
+        let mut reached: FnvHashSet<_> = roots.iter().collect();
+        let mut stack: Vec<_> = roots.iter().collect();
+
+        while let Some(node) = stack.pop() {
+            for prev in self.predecessors(node) {
+                if reached.insert(prev) {
+                    stack.push(prev);
+                }
+            }
+        }
+
+        let mut unreachable: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|id| !reached.contains(id))
+            .cloned()
+            .collect();
+
+        unreachable.sort_unstable();
+        unreachable
+    }
+
+    /// Returns whether `to` can be reached from `from` by following at most `max_depth` edges.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at `to`, or at a node it can be reached from
+    pub fn reachable_within(&self, from: &NodeID, to: &NodeID, max_depth: usize) -> bool {
+        let mut visited = FnvHashSet::from_iter([to]);
+        let mut frontier = vec![to];
+        let mut depth = 0;
+
+        // breadth-first, walking edges backwards, from `to`, since
+        // that's the direction they're stored in
+        loop {
+            if frontier.contains(&from) {
+                return true;
+            }
+
+            if frontier.is_empty() || depth == max_depth {
+                return false;
+            }
+
+            depth += 1;
+
+            frontier = frontier
+                .into_iter()
+                .flat_map(|node| self.predecessors(node))
+                .filter(|&node| visited.insert(node))
+                .collect();
+        }
+    }
+
+    /// Returns the latency accumulated by the signal along the highest latency path from
+    /// `from` to `to`, or `None` if `to` can't be reached from `from`.
+    ///
+    /// This is the sum of the latencies of every node on that path, `to` excluded, bypassed
+    /// nodes having none, i.e., the delay between a signal entering `from` and reaching
+    /// `to`'s inputs.
+    ///
+    /// # Panics
+    ///
+    /// - if no node exists at `from` or `to`
+    /// - if the graph contains a cycle, see [`find_any_cycle`](Self::find_any_cycle)
+    pub fn path_latency(&self, from: &NodeID, to: &NodeID) -> Option<u64> {
+        assert!(
+            self.nodes.contains_key(from) && self.nodes.contains_key(to),
+            "no node found for this id",
+        );
+
+        let order = self.topological_order().expect("graph contains a cycle");
+        let mut latencies = FnvHashMap::from_iter([(from.clone(), 0)]);
+
+        for node in order {
+            let latency = self
+                .predecessors(&node)
+                .filter_map(|prev| {
+                    let node = &self[prev];
+                    let latency = if node.bypassed { 0 } else { node.latency };
+                    Some(latencies.get(prev)? + latency)
+                })
+                .max();
+
+            if let Some(latency) = latency {
+                latencies.entry(node).or_insert(latency);
+            }
+        }
+
+        latencies.remove(to)
+    }
+
+    /// Returns the graph's longest chain of nodes, in signal flow order, along with the sum of
+    /// their latencies, bypassed nodes having none. Among chains of the same length, the one with
+    /// the highest latency is returned.
+    ///
+    /// Returns `None` if the graph contains a cycle, see [`find_any_cycle`](Self::find_any_cycle).
+    pub fn critical_path(&self) -> Option<(Vec<NodeID>, u64)> {
+        let order = self.topological_order()?;
+
+        // the length and latency of the longest chain ending at each node, and the node before it
+        let mut chains = FnvHashMap::<NodeID, (usize, u64, Option<NodeID>)>::default();
+
+        for node_id in order {
+            let node = &self[&node_id];
+            let latency = if node.bypassed { 0 } else { node.latency };
+
+            let (len, chain_latency, prev) = self
+                .predecessors(&node_id)
+                .filter_map(|prev| {
+                    chains
+                        .get(prev)
+                        .map(|&(len, latency, _)| (len, latency, prev))
+                })
+                .max()
+                .map_or((0, 0, None), |(len, latency, prev)| {
+                    (len, latency, Some(prev.clone()))
+                });
+
+            chains.insert(node_id, (len + 1, chain_latency + latency, prev));
+        }
+
+        let Some((mut current, &(_, latency, _))) = chains
+            .iter()
+            .max_by_key(|(id, &(len, latency, _))| (len, latency, *id))
+        else {
+            return Some((vec![], 0));
+        };
+
+        let mut path = vec![current.clone()];
+
+        while let Some(prev) = &chains[current].2 {
+            path.push(prev.clone());
+            current = prev;
+        }
+
+        path.reverse();
+
+        Some((path, latency))
+    }
+
+    /// Returns whether an edge from any of `from`'s outputs to any of `to`'s inputs
+    /// would create a cycle, without inserting it.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at either `from` or `to`
+    #[inline]
+    pub fn would_create_cycle(&self, from: &NodeID, to: &NodeID) -> bool {
+        self.find_path(from, to).is_some()
+    }
+
+    /// Returns the nodes on a path going from `to` to `from`, in signal flow order,
+    /// if there is one.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at either `from` or `to`
+    fn find_path(&self, from: &NodeID, to: &NodeID) -> Option<Vec<NodeID>> {
+        // maps every visited node to the one it was reached from
+        let mut visited = FnvHashMap::from_iter([(from, from)]);
+        let mut stack = vec![from];
+
+        while let Some(node) = stack.pop() {
+            if node == to {
+                let mut path = vec![node.clone()];
+                let mut current = node;
+
+                while current != from {
+                    current = visited[current];
+                    path.push(current.clone());
+                }
+
+                return Some(path);
+            }
+
+            for port in self.get_node(node).unwrap().inputs().values() {
+                for prev in port.connections().keys() {
+                    if let Entry::Vacant(e) = visited.entry(prev) {
+                        e.insert(node);
+                        stack.push(prev);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns an iterator over every edge in the graph, each one
+    /// yielded once, as a `(source output, destination input)` pair.
+    #[inline]
+    pub fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.nodes.iter().flat_map(|(node_id, node)| {
+            node.inputs().iter().flat_map(move |(input_id, input)| {
+                input
+                    .connections()
+                    .iter()
+                    .flat_map(move |(source_id, output_ids)| {
+                        output_ids.iter().map(move |output_id| {
+                            (
+                                (source_id.clone(), output_id.clone()),
+                                (node_id.clone(), input_id.clone()),
+                            )
+                        })
+                    })
+            })
+        })
+    }
+
+    /// Returns whether the graph has no cycles, see [`find_any_cycle`](Self::find_any_cycle).
+    #[inline]
+    pub fn is_acyclic(&self) -> bool {
+        self.find_any_cycle().is_none()
+    }
+
+    /// Searches the whole graph for a cycle, returning the nodes forming it, in signal flow
+    /// order, if one is found. Edges starting at missing nodes are ignored.
+    ///
+    /// Cycles can't be created through this API, but may exist in graphs loaded from elsewhere.
+    pub fn find_any_cycle(&self) -> Option<Vec<NodeID>> {
+        let predecessors = |node| -> Vec<_> {
+            self.predecessors(node)
+                .filter(|&prev| self.nodes.contains_key(prev))
+                .collect()
+        };
+
+        // nodes on the current path are mapped to false, fully explored nodes to true
+        let mut explored = FnvHashMap::<&NodeID, bool>::default();
+
+        for root in self.nodes.keys() {
+            if explored.contains_key(root) {
+                continue;
+            }
+
+            explored.insert(root, false);
+            // each node on the path feeds the one before it
+            let mut path = vec![(root, predecessors(root))];
+
+            while let Some((node, remaining)) = path.last_mut() {
+                let Some(prev) = remaining.pop() else {
+                    explored.insert(node, true);
+                    path.pop();
+                    continue;
+                };
+
+                match explored.get(prev) {
+                    Some(true) => (),
+                    Some(false) => {
+                        let start = path.iter().position(|&(node, _)| node == prev).unwrap();
+
+                        return Some(
+                            path[start..]
+                                .iter()
+                                .rev()
+                                .map(|(node, _)| (*node).clone())
+                                .collect(),
+                        );
+                    }
+                    None => {
+                        explored.insert(prev, false);
+                        path.push((prev, predecessors(prev)));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns every [required](Input::is_required) input that isn't connected to anything,
+    /// sorted by node, then input, ID.
+    pub fn unsatisfied_inputs(&self) -> Vec<(NodeID, InputID)> {
+        let mut inputs: Vec<_> = self
+            .nodes
+            .iter()
+            .flat_map(|(node_id, node)| {
+                node.inputs()
+                    .iter()
+                    .filter(|(_, input)| input.is_required() && input.connections().is_empty())
+                    .map(|(input_id, _)| (node_id.clone(), input_id.clone()))
+            })
+            .collect();
+
+        inputs.sort_unstable();
+        inputs
+    }
+
+    /// Checks that every edge in the graph starts at an existing output, returning all
+    /// the offending edges otherwise.
+    ///
+    /// Edges are stored in their destination input, so only their source can dangle. This
+    /// can't happen through this API, but may in graphs loaded from elsewhere.
+    pub fn validate(&self) -> Result<(), Vec<PortError>> {
+        let errors: Vec<_> = self
+            .edges()
+            .filter_map(|edge| {
+                let ((node_id, output_id), _) = &edge;
+
+                match self.get_node(node_id) {
+                    None => Some(PortError::NodeNotFound(edge)),
+                    Some(node) if !node.output_ids().contains(output_id) => {
+                        Some(PortError::OutputNotFound(edge))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns a hash of the graph's structure: it's nodes' IDs, port layouts, latencies,
+    /// bypass states and connections. Metadata isn't taken into account.
+    ///
+    /// Unlike hashing the graph's maps directly, this doesn't depend on the order in
+    /// which nodes and edges were inserted, so structurally equal graphs hash equally.
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = FnvHasher::default();
+
+        let mut node_ids: Vec<_> = self.nodes.keys().collect();
+        node_ids.sort_unstable();
+
+        for node_id in node_ids {
+            let node = &self[node_id];
+
+            node_id.hash(&mut hasher);
+            node.latency.hash(&mut hasher);
+            node.bypassed.hash(&mut hasher);
+
+            let mut output_ids: Vec<_> = node.output_ids().iter().collect();
+            output_ids.sort_unstable();
+
+            for output_id in output_ids {
+                output_id.hash(&mut hasher);
+                node.output_kind(output_id).hash(&mut hasher);
+            }
+
+            let mut inputs: Vec<_> = node.inputs().iter().collect();
+            inputs.sort_unstable_by_key(|&(input_id, _)| input_id);
+
+            for (input_id, input) in inputs {
+                let mut sources: Vec<_> = input
+                    .connections()
+                    .iter()
+                    .flat_map(|(node_id, output_ids)| iter::repeat(node_id).zip(output_ids))
+                    .collect();
+                sources.sort_unstable();
+
+                input_id.hash(&mut hasher);
+                input.max_fan_in().hash(&mut hasher);
+                input.kind().hash(&mut hasher);
+                input.is_required().hash(&mut hasher);
+                sources.hash(&mut hasher);
+            }
+        }
+
+        hasher.finish()
+    }
+
+    /// Returns a copy of the graph's nodes' port layouts, settings, and edges,
+    /// that can be kept, e.g. by a UI thread, independently from the graph.
+    pub fn snapshot(&self) -> GraphSnapshot {
+        let mut nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(id, node)| {
+                let mut input_ids: Vec<_> = node.inputs().keys().cloned().collect();
+                let mut output_ids: Vec<_> = node.output_ids().iter().cloned().collect();
+
+                input_ids.sort_unstable();
+                output_ids.sort_unstable();
+
+                NodeSnapshot {
+                    id: id.clone(),
+                    latency: node.latency,
+                    bypassed: node.bypassed,
+                    input_ids,
+                    output_ids,
+                }
+            })
+            .collect();
+
+        let mut edges: Vec<_> = self.edges().collect();
+
+        nodes.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+        edges.sort_unstable();
+
+        GraphSnapshot { nodes, edges }
+    }
+
+    /// Returns every edge between the nodes at `a` and `b`, in either direction.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at `a` or `b`
+    pub fn parallel_edges(&self, a: &NodeID, b: &NodeID) -> Vec<Edge> {
+        let mut edges = vec![];
+
+        for (from, to) in [(a, b), (b, a)] {
+            for (input_id, input) in self[to].inputs() {
+                if let Some(output_ids) = input.connections().get(from) {
+                    edges.extend(output_ids.iter().map(|output_id| {
+                        (
+                            (from.clone(), output_id.clone()),
+                            (to.clone(), input_id.clone()),
+                        )
+                    }));
+                }
+            }
+        }
+
+        edges
+    }
+
+    /// Returns the number of edges ending at one of `node`'s inputs.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at `node`
+    #[inline]
+    pub fn in_degree(&self, node: &NodeID) -> usize {
+        self[node]
+            .inputs()
+            .values()
+            .flat_map(|input| input.connections().values())
+            .map(FnvHashSet::len)
+            .sum()
+    }
+
+    /// Returns the number of edges starting at one of `node`'s outputs.
+    ///
+    /// Since edges are only stored at their destination, this visits the entire graph.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at `node`
+    #[inline]
+    pub fn out_degree(&self, node: &NodeID) -> usize {
+        assert!(self.nodes.contains_key(node), "no node found for this id");
+
+        self.edges().filter(|((from, _), _)| from == node).count()
+    }
+
+    /// Returns whether the output `output_id` of the node at `node_id` is connected to more
+    /// than one input. In compiled schedules, such inputs all read the output's buffer directly,
+    /// without it being copied, unless they need to be delayed by different amounts.
+    pub fn is_shared_output(&self, node_id: &NodeID, output_id: &OutputID) -> bool {
+        self.nodes
+            .values()
+            .flat_map(|node| node.inputs().values())
+            .filter(|input| {
+                input
+                    .connections()
+                    .get(node_id)
+                    .is_some_and(|output_ids| output_ids.contains(output_id))
+            })
+            .nth(1)
+            .is_some()
+    }
+
+    /// Returns the IDs of the nodes with an edge into `node`, each one once.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at `node`
+    #[inline]
+    pub fn predecessors(&self, node: &NodeID) -> impl Iterator<Item = &NodeID> {
+        let predecessors: FnvHashSet<_> = self[node]
+            .inputs()
+            .values()
+            .flat_map(|input| input.connections().keys())
+            .collect();
+
+        predecessors.into_iter()
+    }
+
+    /// Returns the IDs of the nodes with an edge coming from `node`, each one once.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at `node`
+    #[inline]
+    pub fn successors<'a>(&'a self, node: &'a NodeID) -> impl Iterator<Item = &'a NodeID> {
+        assert!(self.nodes.contains_key(node), "no node found for this id");
+
+        self.nodes
+            .iter()
+            .filter(|(_, other)| {
+                other
+                    .inputs()
+                    .values()
+                    .any(|input| input.connections().contains_key(node))
+            })
+            .map(|(id, _)| id)
+    }
+
+    /// Returns the IDs of the nodes with no edge into them, sorted. Nodes with no connections
+    /// at all are both sources and [sinks](Self::sinks).
+    pub fn sources(&self) -> Vec<NodeID> {
+        let mut sources: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| {
+                node.inputs()
+                    .values()
+                    .all(|input| input.connections().is_empty())
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        sources.sort_unstable();
+        sources
+    }
+
+    /// Returns the IDs of the nodes with no edge coming from them, sorted. These are the
+    /// nodes that usually need to be passed as roots to [`compile`](Self::compile).
+    pub fn sinks(&self) -> Vec<NodeID> {
+        let feeding: FnvHashSet<_> = self
+            .nodes
+            .values()
+            .flat_map(|node| node.inputs().values())
+            .flat_map(|input| input.connections().keys())
+            .collect();
+
+        let mut sinks: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|id| !feeding.contains(id))
+            .cloned()
+            .collect();
+
+        sinks.sort_unstable();
+        sinks
+    }
+
+    #[inline]
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn get_metadata(&self, id: &NodeID) -> Option<&M> {
+        self.metadata.get(id)
+    }
+
+    #[inline]
+    pub fn get_metadata_mut(&mut self, id: &NodeID) -> Option<&mut M> {
+        self.metadata.get_mut(id)
+    }
+
+    /// Tags the node at `id` with `metadata`, returning it's previous metadata, if any.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at `id`
+    #[inline]
+    pub fn set_metadata(&mut self, id: &NodeID, metadata: M) -> Option<M> {
+        assert!(self.nodes.contains_key(id), "no node found for this id");
+
+        self.metadata.insert(id.clone(), metadata)
+    }
+
+    #[inline]
+    pub fn remove_metadata(&mut self, id: &NodeID) -> Option<M> {
+        self.metadata.remove(id)
+    }
+
+    #[inline]
+    pub fn get_node(&self, index: &NodeID) -> Option<&Node> {
+        self.nodes.get(index)
+    }
+
+    #[inline]
+    pub fn get_node_mut(&mut self, index: &NodeID) -> Option<&mut Node> {
+        self.nodes.get_mut(index)
+    }
+
+    #[inline]
+    fn try_insert_node(&mut self, id: NodeID, node: Node) -> Result<&mut Node, (&mut Node, Node)> {
+        match self.nodes.entry(id) {
+            Entry::Occupied(e) => Err((e.into_mut(), node)),
+            Entry::Vacant(e) => Ok(e.insert(node)),
+        }
+    }
+
+    /// Removes the node at `id`, along with it's metadata and every edge connected to it.
+    ///
+    /// Returns the removed node, stripped of it's connections, and all the edges that were
+    /// removed in the process, so that both can be inserted back later.
+    #[inline]
+    pub fn remove_node(&mut self, id: &NodeID) -> Option<(Node, Vec<Edge>)> {
+        let mut node = self.nodes.remove(id)?;
+        // the ID may be reused, so the metadata musn't outlive the node
+        self.metadata.remove(id);
+        self.insertion_order.remove(id);
+
+        let mut edges = vec![];
+
+        for (input_id, input) in node.inputs.iter_mut() {
+            for (node_id, ports) in mem::take(&mut input.connections) {
+                edges.extend(
+                    ports
+                        .into_iter()
+                        .map(|port| ((node_id.clone(), port), (id.clone(), input_id.clone()))),
+                );
+            }
+        }
+
+        for (node_id, other) in self.nodes.iter_mut() {
+            for (input_id, input) in other.inputs.iter_mut() {
+                if let Some(ports) = input.connections.remove(id) {
+                    edges.extend(
+                        ports
+                            .into_iter()
+                            .map(|port| ((id.clone(), port), (node_id.clone(), input_id.clone()))),
+                    );
+                }
+            }
+        }
+
+        Some((node, edges))
+    }
+
+    /// Removes every edge in the graph, keeping all nodes, their ports and their metadata.
+    pub fn clear_connections(&mut self) {
+        for input in self
+            .nodes
+            .values_mut()
+            .flat_map(|node| node.inputs.values_mut())
+        {
+            input.connections.clear();
+        }
+    }
+
+    /// Removes every edge ending at the input `input_id` of the node at `node_id`.
+    ///
+    /// Returns the outputs that were connected to it. If there is no such input,
+    /// nothing happens, and an empty list is returned.
+    pub fn disconnect_input(
+        &mut self,
+        node_id: &NodeID,
+        input_id: &InputID,
+    ) -> Vec<(NodeID, OutputID)> {
+        let Some(input) = self
+            .get_node_mut(node_id)
+            .and_then(|node| node.get_input_mut(input_id))
+        else {
+            return vec![];
+        };
+
+        mem::take(&mut input.connections)
+            .into_iter()
+            .flat_map(|(node_id, output_ids)| iter::repeat(node_id).zip(output_ids))
+            .collect()
+    }
+
+    /// Removes every edge starting at the output `output_id` of the node at `node_id`.
+    ///
+    /// Returns the inputs that were connected to it. If there is no such output,
+    /// nothing happens, and an empty list is returned.
+    pub fn disconnect_output(
+        &mut self,
+        node_id: &NodeID,
+        output_id: &OutputID,
+    ) -> Vec<(NodeID, InputID)> {
+        let mut inputs = vec![];
+
+        for (other_id, other) in self.nodes.iter_mut() {
+            for (input_id, input) in other.inputs.iter_mut() {
+                if input.remove_port((node_id, output_id)) {
+                    inputs.push((other_id.clone(), input_id.clone()));
+                }
+            }
+        }
+
+        inputs
+    }
+
+    /// Returns every edge connected to a port of the node at `node_id` with an ID greater than
+    /// or equal to `threshold`, sorted. These are the edges removing those ports would remove.
+    pub fn edges_on_ports_above(&self, node_id: &NodeID, threshold: u32) -> Vec<Edge> {
+        let mut edges: Vec<_> = self
+            .edges()
+            .filter(
+                |((from_node, OutputID(output_id)), (to_node, InputID(input_id)))| {
+                    (from_node == node_id && *output_id >= threshold)
+                        || (to_node == node_id && *input_id >= threshold)
+                },
+            )
+            .collect();
+
+        edges.sort_unstable();
+        edges
+    }
+
+    /// Removes the input `input_id` from the node at `node_id`, along with every edge ending
+    /// at it, returning the outputs that were connected to it, or `None` if there is no such input.
+    pub fn remove_input(
+        &mut self,
+        node_id: &NodeID,
+        input_id: &InputID,
+    ) -> Option<Vec<(NodeID, OutputID)>> {
+        let input = self.get_node_mut(node_id)?.remove_input(input_id)?;
+
+        Some(
+            input
+                .connections
+                .into_iter()
+                .flat_map(|(node_id, output_ids)| iter::repeat(node_id).zip(output_ids))
+                .collect(),
+        )
+    }
+
+    /// Removes the output `output_id` from the node at `node_id`, along with every edge starting
+    /// at it, returning the inputs that were connected to it, or `None` if there is no such output.
+    pub fn remove_output(
+        &mut self,
+        node_id: &NodeID,
+        output_id: &OutputID,
+    ) -> Option<Vec<(NodeID, InputID)>> {
+        if !self.get_node_mut(node_id)?.remove_output(output_id) {
+            return None;
+        }
+
+        Some(self.disconnect_output(node_id, output_id))
+    }
+
+    /// Replaces the node at `id` with `node`, keeping it's metadata, and every edge connected
+    /// to a port that `node` also has. Edges connected to ports missing from `node` are removed.
+    ///
+    /// Returns the replaced node, stripped of it's connections, or gives `node` back if
+    /// no node exists at `id`.
+    pub fn replace_node(&mut self, id: &NodeID, mut node: Node) -> Result<Node, Node> {
+        let Some(old) = self.get_node_mut(id) else {
+            return Err(node);
+        };
+
+        for (input_id, input) in node.inputs.iter_mut() {
+            input.connections = old
+                .get_input_mut(input_id)
+                .map(|old_input| mem::take(&mut old_input.connections))
+                .unwrap_or_default();
+        }
+
+        let old = mem::replace(old, node).without_connections();
+        let output_ids = self[id].output_ids().clone();
+
+        for other in self.nodes.values_mut() {
+            for input in other.inputs.values_mut() {
+                if let Entry::Occupied(mut e) = input.connections.entry(id.clone()) {
+                    e.get_mut()
+                        .retain(|output_id| output_ids.contains(output_id));
+
+                    if e.get().is_empty() {
+                        e.remove();
+                    }
+                }
+            }
+        }
+
+        Ok(old)
+    }
+
+    /// Returns the ID [`insert_node`](Self::insert_node) will insert the next node at,
+    /// the lowest one not in use.
+    #[inline]
+    pub fn next_node_id(&self) -> NodeID {
         #[allow(clippy::useless_conversion)]
-        let all_numbers = (0..).into_iter().map(NodeID);
+        let mut all_numbers = (0..).into_iter().map(NodeID);
+
+        all_numbers
+            .find(|i| !self.nodes.contains_key(i))
+            .expect("Index overflow")
+    }
+
+    /// Returns the number of unused IDs lower than the highest one in use,
+    /// i.e. left behind by removed nodes, and to be reused by the next insertions.
+    #[inline]
+    pub fn num_holes(&self) -> usize {
+        self.nodes
+            .keys()
+            .max()
+            .map_or(0, |NodeID(max)| *max as usize + 1 - self.nodes.len())
+    }
+
+    #[inline]
+    pub fn insert_node(&mut self, node: Node) -> NodeID {
+        let id = self.next_node_id();
+        self.nodes.insert(id.clone(), node);
+        self.record_insertion(&id);
+        id
+    }
+
+    /// Gives the newly inserted node at `id` a sequence number higher than every other node's.
+    #[inline]
+    fn record_insertion(&mut self, id: &NodeID) {
+        self.insertion_order.insert(id.clone(), self.next_sequence);
+        self.next_sequence += 1;
+    }
+
+    /// Returns the IDs of every node in the graph, in the order they were inserted in.
+    ///
+    /// Nodes inserted at IDs left behind by removed nodes are treated as new, and come last.
+    pub fn nodes_by_insertion_order(&self) -> Vec<NodeID> {
+        let mut node_ids: Vec<_> = self.nodes.keys().collect();
+        // nodes of graphs deserialized without an insertion order come first
+        node_ids.sort_unstable_by_key(|&id| (self.insertion_order.get(id), id));
+        node_ids.into_iter().cloned().collect()
+    }
+
+    /// Renumbers the graph's nodes with the IDs `0..self.num_nodes()`, keeping their relative
+    /// order, removing every hole left by removed nodes. Edges and metadata follow their nodes.
+    /// Connections from, and metadata of, missing nodes, which only graphs loaded from
+    /// elsewhere may have (see [`validate`](Self::validate)), are dropped.
+    ///
+    /// Returns the new ID of each node, keyed by it's old one.
+    pub fn compact(&mut self) -> FnvHashMap<NodeID, NodeID> {
+        let mut old_ids: Vec<_> = self.nodes.keys().cloned().collect();
+        old_ids.sort_unstable();
+
+        let new_ids: FnvHashMap<_, _> = old_ids.into_iter().zip((0..).map(NodeID)).collect();
+
+        self.nodes = mem::take(&mut self.nodes)
+            .into_iter()
+            .map(|(id, mut node)| {
+                for input in node.inputs.values_mut() {
+                    input.connections = mem::take(&mut input.connections)
+                        .into_iter()
+                        .filter_map(|(node_id, output_ids)| {
+                            Some((new_ids.get(&node_id)?.clone(), output_ids))
+                        })
+                        .collect();
+                }
+
+                (new_ids[&id].clone(), node)
+            })
+            .collect();
+
+        self.metadata = mem::take(&mut self.metadata)
+            .into_iter()
+            .filter_map(|(id, metadata)| Some((new_ids.get(&id)?.clone(), metadata)))
+            .collect();
+
+        self.insertion_order = mem::take(&mut self.insertion_order)
+            .into_iter()
+            .filter_map(|(id, sequence)| Some((new_ids.get(&id)?.clone(), sequence)))
+            .collect();
+
+        new_ids
+    }
+}
+
+impl<M> AudioGraph<M> {
+    /// Renders the graph in Graphviz's DOT format.
+    ///
+    /// Nodes are drawn as records, with their inputs on top and their outputs at the bottom,
+    /// so that every edge attaches to the exact ports it connects.
+    pub fn to_dot(&self) -> String {
+        fn record_ports<'a>(prefix: char, ids: impl IntoIterator<Item = &'a u32>) -> String {
+            let mut ids: Vec<_> = ids.into_iter().collect();
+            ids.sort_unstable();
+
+            let ports: Vec<_> = ids.iter().map(|i| format!("<{prefix}{i}> {i}")).collect();
+
+            ports.join("|")
+        }
+
+        let mut node_ids: Vec<_> = self.nodes.keys().collect();
+        node_ids.sort_unstable();
+
+        let mut dot = String::from("digraph {\n    node [shape = record];\n");
+
+        for node_id in &node_ids {
+            let node = &self[node_id];
+
+            let inputs = record_ports('i', node.inputs().keys().map(|id| &id.0));
+            let outputs = record_ports('o', node.output_ids().iter().map(|id| &id.0));
+
+            writeln!(
+                dot,
+                "    n{0} [label = \"{{{{{inputs}}}|{0}|{{{outputs}}}}}\"];",
+                node_id.0,
+            )
+            .unwrap();
+        }
+
+        let mut edges: Vec<_> = self.edges().collect();
+        edges.sort_unstable();
 
-        for i in all_numbers {
-            if !self.nodes.contains_key(&i) {
-                self.nodes.insert(i.clone(), node);
-                return i;
+        for ((from_node, output_id), (to_node, input_id)) in edges {
+            writeln!(
+                dot,
+                "    n{}:o{} -> n{}:i{};",
+                from_node.0, output_id.0, to_node.0, input_id.0,
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<M: Clone> AudioGraph<M> {
+    /// Inserts a disconnected copy of each of `other`'s nodes at `node_ids`, along with their
+    /// metadata, then copies the edges between them, returning the IDs each copy was given.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists in `other` at one of `node_ids`
+    fn insert_subgraph<'a>(
+        &mut self,
+        other: &Self,
+        node_ids: impl IntoIterator<Item = &'a NodeID>,
+    ) -> FnvHashMap<NodeID, NodeID> {
+        let mut new_ids = FnvHashMap::default();
+
+        for node_id in node_ids {
+            if let Entry::Vacant(e) = new_ids.entry(node_id.clone()) {
+                let new_id = self.insert_node(other[node_id].without_connections());
+
+                if let Some(metadata) = other.get_metadata(node_id) {
+                    self.metadata.insert(new_id.clone(), metadata.clone());
+                }
+
+                e.insert(new_id);
             }
         }
 
-        panic!("Index overflow")
+        for ((from_node, output_id), (to_node, input_id)) in other.edges() {
+            if let (Some(from_node), Some(to_node)) =
+                (new_ids.get(&from_node), new_ids.get(&to_node))
+            {
+                // no need to check for cycles here, since other is acyclic
+                self.get_node_mut(to_node)
+                    .unwrap()
+                    .get_input_mut(&input_id)
+                    .unwrap()
+                    .insert_output((from_node.clone(), output_id));
+            }
+        }
+
+        new_ids
+    }
+
+    /// Copies the nodes at `nodes`, their metadata, and the edges between them, into a new graph.
+    ///
+    /// Returns the new graph, and the IDs the nodes were given in it.
+    ///
+    /// # Panics
+    ///
+    /// if no node exists at one of `nodes`
+    #[inline]
+    pub fn extract_subgraph(&self, nodes: &[NodeID]) -> (Self, FnvHashMap<NodeID, NodeID>) {
+        let mut subgraph = Self::new();
+        let new_ids = subgraph.insert_subgraph(self, nodes);
+
+        (subgraph, new_ids)
+    }
+
+    /// Returns a copy of this graph, with the same node IDs, ports and metadata, but no edges.
+    #[inline]
+    pub fn clone_structure(&self) -> Self {
+        Self {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(id, node)| (id.clone(), node.without_connections()))
+                .collect(),
+            metadata: self.metadata.clone(),
+            insertion_order: self.insertion_order.clone(),
+            next_sequence: self.next_sequence,
+        }
+    }
+
+    /// Inserts a copy of every node, edge and metadata in `other` into this graph.
+    ///
+    /// Returns the IDs the copied nodes were given, which generally differ from their IDs in `other`.
+    #[inline]
+    pub fn append_graph(&mut self, other: &Self) -> FnvHashMap<NodeID, NodeID> {
+        self.insert_subgraph(other, other.nodes.keys())
+    }
+}
+
+impl<M: Clone + PartialEq> AudioGraph<M> {
+    /// Returns the commands transforming this graph into `new`, to be
+    /// [restored](Self::restore) in order. Nodes are matched by ID.
+    ///
+    /// Nodes whose ports differ are removed then inserted back, nodes whose settings only
+    /// differ are replaced in place, and other nodes are kept. Replaced and kept nodes keep
+    /// the edges between them they still share. Edges are removed before nodes, and inserted
+    /// after them, so the graph never becomes cyclic in between commands.
+    pub fn diff(&self, new: &Self) -> Vec<GraphCommand<M>> {
+        let ports = |node: &Node| Node {
+            latency: 0,
+            bypassed: false,
+            ..node.without_connections()
+        };
+
+        let kept = |id: &NodeID| {
+            self.get_node(id)
+                .zip(new.get_node(id))
+                .is_some_and(|(old, new)| ports(old) == ports(new))
+        };
+
+        let old_edges: FnvHashSet<_> = self.edges().collect();
+        let new_edges: FnvHashSet<_> = new.edges().collect();
+
+        let mut removed_nodes: Vec<_> = self.nodes.keys().filter(|id| !kept(id)).collect();
+        let mut inserted_nodes: Vec<_> = new.nodes.keys().filter(|id| !kept(id)).collect();
+
+        let mut replaced_nodes: Vec<_> = new
+            .nodes
+            .keys()
+            .filter(|id| {
+                kept(id) && self[id].without_connections() != new[id].without_connections()
+            })
+            .collect();
+
+        let mut updated_metadata: Vec<_> = new
+            .nodes
+            .keys()
+            .filter(|id| kept(id) && self.get_metadata(id) != new.get_metadata(id))
+            .collect();
+
+        // edges connected to removed nodes are removed along with them
+        let mut removed_edges: Vec<_> = old_edges
+            .iter()
+            .filter(|((from, _), (to, _))| kept(from) && kept(to))
+            .filter(|edge| !new_edges.contains(edge))
+            .cloned()
+            .collect();
+
+        let mut inserted_edges: Vec<_> = new_edges
+            .iter()
+            .filter(|edge @ ((from, _), (to, _))| {
+                !(kept(from) && kept(to) && old_edges.contains(edge))
+            })
+            .cloned()
+            .collect();
+
+        removed_nodes.sort_unstable();
+        inserted_nodes.sort_unstable();
+        replaced_nodes.sort_unstable();
+        updated_metadata.sort_unstable();
+        removed_edges.sort_unstable();
+        inserted_edges.sort_unstable();
+
+        let removed_edges = removed_edges.into_iter().map(GraphCommand::RemoveEdge);
+
+        let removed_nodes = removed_nodes
+            .into_iter()
+            .map(|id| GraphCommand::RemoveNode(id.clone()));
+
+        let inserted_nodes = inserted_nodes
+            .into_iter()
+            .map(|id| GraphCommand::InsertNode {
+                id: Some(id.clone()),
+                node: new[id].without_connections(),
+                metadata: new.get_metadata(id).cloned(),
+                edges: vec![],
+            });
+
+        let replaced_nodes = replaced_nodes
+            .into_iter()
+            .map(|id| GraphCommand::ReplaceNode {
+                id: id.clone(),
+                node: new[id].without_connections(),
+                edges: vec![],
+                removed_edges: vec![],
+            });
+
+        let updated_metadata = updated_metadata
+            .into_iter()
+            .map(|id| GraphCommand::SetMetadata(id.clone(), new.get_metadata(id).cloned()));
+
+        let inserted_edges = inserted_edges.into_iter().map(GraphCommand::InsertEdge);
+
+        removed_edges
+            .chain(removed_nodes)
+            .chain(replaced_nodes)
+            .chain(inserted_nodes)
+            .chain(updated_metadata)
+            .chain(inserted_edges)
+            .collect()
     }
 }
@@ -1,5 +1,5 @@
 use super::*;
-use core::{array, convert::identity as id, iter::zip, ops::Not};
+use core::{array, convert::identity as id, iter::zip, ops::Not, slice};
 
 // These tests aren't ideal, I have to print the compiled schedule and review it first,
 // then insert it as the rhs of the final assert directive if it's correct. This is inconvenient,
@@ -28,7 +28,7 @@ fn basic_cycle() {
         .is_ok_and(id));
     assert!(graph
         .try_insert_edge((node1_id, node1_output_id), (node2_id, node2_input_id))
-        .is_err_and(id));
+        .is_err_and(|e| matches!(e, EdgeInsertError::CycleFound(_))));
 }
 
 #[test]
@@ -433,3 +433,2292 @@ fn mutiple_input_ports() {
 
     assert_eq!(num_buffers, 1);
 }
+
+#[test]
+fn topological_order() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    let isolated_id = graph.insert_node(Node::default());
+
+    assert!(graph
+        .try_insert_edge(
+            (node_id.clone(), node_output_id),
+            (master_id.clone(), master_input_id),
+        )
+        .is_ok_and(id));
+
+    let order = graph.topological_order().unwrap();
+
+    assert_eq!(order.len(), 3);
+    assert_eq!(order.iter().filter(|&id| id == &isolated_id).count(), 1);
+
+    let position = |node| order.iter().position(|id| id == node).unwrap();
+
+    assert!(position(&node_id) < position(&master_id));
+    assert_eq!(Some(order), graph.topological_order());
+}
+
+#[test]
+fn dot_export() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    node.add_input();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    assert!(graph
+        .try_insert_edge((node_id, node_output_id), (master_id, master_input_id))
+        .is_ok_and(id));
+
+    assert_eq!(
+        graph.to_dot(),
+        "digraph {
+    node [shape = record];
+    n0 [label = \"{{<i0> 0}|0|{}}\"];
+    n1 [label = \"{{<i0> 0}|1|{<o0> 0}}\"];
+    n1:o0 -> n0:i0;
+}
+",
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let [(left_output_id, left_id), (right_output_id, right_id)] = array::from_fn(|_| {
+        let mut node = Node {
+            latency: 64,
+            ..Default::default()
+        };
+        (node.add_output(), graph.insert_node(node))
+    });
+
+    assert!(graph
+        .try_insert_edge(
+            (left_id, left_output_id),
+            (master_id.clone(), master_input_id.clone()),
+        )
+        .is_ok_and(id));
+    assert!(graph
+        .try_insert_edge((right_id, right_output_id), (master_id, master_input_id))
+        .is_ok_and(id));
+
+    let json = serde_json::to_string(&graph).unwrap();
+    let deserialized: AudioGraph = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(graph, deserialized);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_defaulted_fields() {
+    // written before ports had kinds, rules, or nodes could be bypassed
+    let json = r#"{"nodes":{
+        "0":{"latency":64,"output_ids":[0],"inputs":{}},
+        "1":{"latency":0,"output_ids":[],"inputs":{"0":{"connections":{"0":[0]}}}}
+    }}"#;
+
+    let graph: AudioGraph = serde_json::from_str(json).unwrap();
+
+    assert!(!graph[&NodeID(0)].bypassed);
+
+    let input = &graph[&NodeID(1)].inputs()[&InputID(0)];
+    assert_eq!(input.max_fan_in(), None);
+    assert_eq!(input.kind(), PortKind::default());
+    assert!(!input.is_required());
+    assert_eq!(input.fan_in(), 1);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn versioned_graph() {
+    let mut graph = AudioGraph::default();
+
+    let mut node = Node::default();
+    node.add_input();
+    graph.insert_node(node);
+
+    let json = serde_json::to_string(&VersionedGraph::from(graph.clone())).unwrap();
+
+    assert!(json.starts_with(r#"{"version":"1","#));
+
+    let loaded: VersionedGraph = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(loaded.migrate(), graph);
+
+    let future = json.replacen(r#""version":"1""#, r#""version":"2""#, 1);
+
+    assert!(serde_json::from_str::<VersionedGraph>(&future).is_err());
+}
+
+#[test]
+fn remove_node() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut source = Node::default();
+    let source_output_id = source.add_output();
+    let source_id = graph.insert_node(source);
+
+    let mut node = Node::default();
+    let node_input_id = node.add_input();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    let edge_in = (
+        (source_id.clone(), source_output_id),
+        (node_id.clone(), node_input_id.clone()),
+    );
+    let edge_out = (
+        (node_id.clone(), node_output_id.clone()),
+        (master_id.clone(), master_input_id.clone()),
+    );
+
+    for (from, to) in [edge_in.clone(), edge_out.clone()] {
+        assert!(graph.try_insert_edge(from, to).is_ok_and(id));
+    }
+
+    let (removed, mut edges) = graph.remove_node(&node_id).unwrap();
+
+    edges.sort_unstable();
+    assert_eq!(edges, [edge_in, edge_out]);
+
+    assert!(removed.inputs()[&node_input_id].connections().is_empty());
+    assert!(removed.output_ids().contains(&node_output_id));
+
+    assert!(graph.get_node(&node_id).is_none());
+    assert!(graph[&master_id].inputs()[&master_input_id]
+        .connections()
+        .is_empty());
+
+    assert!(graph.remove_node(&node_id).is_none());
+}
+
+#[test]
+fn edges() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_ids: [_; 2] = array::from_fn(|_| node.add_output());
+    let node_id = graph.insert_node(node);
+
+    let mut expected = node_output_ids.map(|output_id| {
+        (
+            (node_id.clone(), output_id),
+            (master_id.clone(), master_input_id.clone()),
+        )
+    });
+
+    for (from, to) in expected.clone() {
+        assert!(graph.try_insert_edge(from, to).is_ok_and(id));
+    }
+
+    let mut edges: Vec<_> = graph.edges().collect();
+
+    edges.sort_unstable();
+    expected.sort_unstable();
+
+    assert_eq!(edges, expected);
+}
+
+#[test]
+fn cycle_path() {
+    let mut graph = AudioGraph::default();
+
+    let [(node1_input_id, node1_output_id, node1_id), (node2_input_id, node2_output_id, node2_id), (node3_input_id, node3_output_id, node3_id)] =
+        array::from_fn(|_| {
+            let mut node = Node::default();
+            (node.add_input(), node.add_output(), graph.insert_node(node))
+        });
+
+    assert!(graph
+        .insert_edge(
+            (node1_id.clone(), node1_output_id),
+            (node2_id.clone(), node2_input_id),
+        )
+        .is_ok_and(id));
+    assert!(graph
+        .insert_edge(
+            (node2_id.clone(), node2_output_id),
+            (node3_id.clone(), node3_input_id),
+        )
+        .is_ok_and(id));
+
+    assert!(graph.would_create_cycle(&node3_id, &node1_id));
+    assert!(graph.would_create_cycle(&node2_id, &node2_id));
+    assert!(!graph.would_create_cycle(&node1_id, &node3_id));
+    assert_eq!(graph.edges().count(), 2);
+
+    assert_eq!(
+        graph.insert_edge(
+            (node3_id.clone(), node3_output_id),
+            (node1_id.clone(), node1_input_id.clone()),
+        ),
+        Err(EdgeInsertError::CycleFound(CycleFound {
+            path: vec![node1_id.clone(), node2_id, node3_id]
+        })),
+    );
+
+    assert_eq!(
+        graph.insert_edge((node1_id.clone(), OutputID(42)), (node1_id, node1_input_id),),
+        Err(EdgeInsertError::PortNotFound),
+    );
+}
+
+#[test]
+fn insert_edges_rollback() {
+    let mut graph = AudioGraph::default();
+
+    let nodes: [_; 3] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    });
+
+    let edge = |(_, output_id, from_id): &(InputID, OutputID, NodeID),
+                (input_id, _, to_id): &(InputID, OutputID, NodeID)| {
+        (
+            (from_id.clone(), output_id.clone()),
+            (to_id.clone(), input_id.clone()),
+        )
+    };
+
+    let [node1, node2, node3] = &nodes;
+
+    let before = graph.clone();
+
+    assert!(matches!(
+        graph.insert_edges(&[edge(node1, node2), edge(node2, node3), edge(node3, node1),]),
+        Err(EdgeInsertError::CycleFound(_)),
+    ));
+
+    assert_eq!(graph, before);
+
+    assert!(graph
+        .insert_edges(&[edge(node1, node2), edge(node2, node3)])
+        .is_ok());
+
+    assert_eq!(graph.edges().count(), 2);
+}
+
+#[test]
+fn degrees() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    for input_id in master_input_ids {
+        assert!(graph
+            .try_insert_edge(
+                (node_id.clone(), node_output_id.clone()),
+                (master_id.clone(), input_id),
+            )
+            .is_ok_and(id));
+    }
+
+    assert_eq!(graph.in_degree(&master_id), 2);
+    assert_eq!(graph.out_degree(&master_id), 0);
+    assert_eq!(graph.in_degree(&node_id), 0);
+    assert_eq!(graph.out_degree(&node_id), 2);
+}
+
+#[test]
+fn validate() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let [(left_output_id, left_id), (right_output_id, right_id)] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_output(), graph.insert_node(node))
+    });
+
+    let left_edge = (
+        (left_id.clone(), left_output_id),
+        (master_id.clone(), master_input_id.clone()),
+    );
+    let right_edge = (
+        (right_id.clone(), right_output_id),
+        (master_id, master_input_id),
+    );
+
+    for (from, to) in [left_edge.clone(), right_edge.clone()] {
+        assert!(graph.try_insert_edge(from, to).is_ok_and(id));
+    }
+
+    assert!(graph.validate().is_ok());
+
+    // break the graph, bypassing the public API
+    graph.nodes.remove(&left_id);
+    graph.nodes.get_mut(&right_id).unwrap().output_ids.clear();
+
+    let mut errors = graph.validate().unwrap_err();
+    errors.sort_unstable_by_key(|e| matches!(e, PortError::OutputNotFound(_)));
+
+    assert_eq!(
+        errors,
+        [
+            PortError::NodeNotFound(left_edge),
+            PortError::OutputNotFound(right_edge),
+        ]
+    );
+}
+
+#[test]
+fn extract_subgraph() {
+    let mut graph = AudioGraph::default();
+
+    let nodes: [_; 3] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    });
+
+    let [(_, node1_output_id, node1_id), (node2_input_id, node2_output_id, node2_id), (node3_input_id, _, node3_id)] =
+        nodes;
+
+    assert!(graph
+        .insert_edges(&[
+            (
+                (node1_id.clone(), node1_output_id),
+                (node2_id.clone(), node2_input_id.clone()),
+            ),
+            (
+                (node2_id.clone(), node2_output_id.clone()),
+                (node3_id.clone(), node3_input_id.clone()),
+            ),
+        ])
+        .is_ok());
+
+    let (subgraph, new_ids) = graph.extract_subgraph(&[node2_id.clone(), node3_id.clone()]);
+
+    assert_eq!(new_ids.len(), 2);
+
+    let new_node2_id = &new_ids[&node2_id];
+    let new_node3_id = &new_ids[&node3_id];
+
+    assert!(subgraph[new_node2_id].inputs()[&node2_input_id]
+        .connections()
+        .is_empty());
+    assert_eq!(
+        subgraph.edges().collect::<Vec<_>>(),
+        [(
+            (new_node2_id.clone(), node2_output_id),
+            (new_node3_id.clone(), node3_input_id),
+        )]
+    );
+}
+
+#[test]
+fn append_graph() {
+    let mut snippet = AudioGraph::default();
+
+    let mut source = Node::default();
+    let source_output_id = source.add_output();
+    let source_id = snippet.insert_node(source);
+
+    let mut sink = Node::default();
+    let sink_input_id = sink.add_input();
+    let sink_id = snippet.insert_node(sink);
+
+    assert!(snippet
+        .try_insert_edge(
+            (source_id.clone(), source_output_id.clone()),
+            (sink_id.clone(), sink_input_id.clone()),
+        )
+        .is_ok_and(id));
+
+    let mut graph = AudioGraph::default();
+    let existing_id = graph.insert_node(Node::default());
+
+    let new_ids = graph.append_graph(&snippet);
+
+    let new_source_id = &new_ids[&source_id];
+    let new_sink_id = &new_ids[&sink_id];
+
+    assert!([new_source_id, new_sink_id]
+        .iter()
+        .all(|&new_id| new_id != &existing_id));
+    assert_eq!(
+        graph.edges().collect::<Vec<_>>(),
+        [(
+            (new_source_id.clone(), source_output_id),
+            (new_sink_id.clone(), sink_input_id),
+        )]
+    );
+}
+
+#[test]
+fn neighbours() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_ids: [_; 2] = array::from_fn(|_| node.add_output());
+    let node_id = graph.insert_node(node);
+
+    for (output_id, input_id) in zip(node_output_ids, master_input_ids) {
+        assert!(graph
+            .try_insert_edge((node_id.clone(), output_id), (master_id.clone(), input_id))
+            .is_ok_and(id));
+    }
+
+    assert_eq!(
+        graph.predecessors(&master_id).collect::<Vec<_>>(),
+        [&node_id]
+    );
+    assert_eq!(graph.successors(&node_id).collect::<Vec<_>>(), [&master_id]);
+    assert!(graph.predecessors(&node_id).next().is_none());
+    assert!(graph.successors(&master_id).next().is_none());
+}
+
+#[test]
+fn reachable_within() {
+    let mut graph = AudioGraph::default();
+
+    let nodes: [_; 3] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    });
+
+    let [(_, node1_output_id, node1_id), (node2_input_id, node2_output_id, node2_id), (node3_input_id, _, node3_id)] =
+        nodes;
+
+    assert!(graph
+        .insert_edges(&[
+            (
+                (node1_id.clone(), node1_output_id),
+                (node2_id.clone(), node2_input_id),
+            ),
+            (
+                (node2_id.clone(), node2_output_id),
+                (node3_id.clone(), node3_input_id),
+            ),
+        ])
+        .is_ok());
+
+    assert!(graph.reachable_within(&node1_id, &node1_id, 0));
+    assert!(graph.reachable_within(&node1_id, &node2_id, 1));
+    assert!(!graph.reachable_within(&node1_id, &node3_id, 1));
+    assert!(graph.reachable_within(&node1_id, &node3_id, 2));
+    assert!(!graph.reachable_within(&node3_id, &node1_id, usize::MAX));
+}
+
+#[test]
+fn deep_chain_cycle() {
+    const CHAIN_LENGTH: usize = 4096;
+
+    let mut graph = AudioGraph::default();
+
+    let nodes: Vec<_> = iter::repeat_with(|| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    })
+    .take(CHAIN_LENGTH)
+    .collect();
+
+    // inserting backwards keeps each cycle check cheap
+    for pair in nodes.windows(2).rev() {
+        let [(_, output_id, from_id), (input_id, _, to_id)] = pair else {
+            unreachable!()
+        };
+
+        assert!(graph
+            .try_insert_edge(
+                (from_id.clone(), output_id.clone()),
+                (to_id.clone(), input_id.clone()),
+            )
+            .is_ok_and(id));
+    }
+
+    let (first_input_id, _, first_id) = nodes.first().unwrap().clone();
+    let (_, last_output_id, last_id) = nodes.last().unwrap().clone();
+
+    let Err(EdgeInsertError::CycleFound(CycleFound { path })) =
+        graph.insert_edge((last_id, last_output_id), (first_id, first_input_id))
+    else {
+        panic!("expected a cycle")
+    };
+
+    assert!(path.into_iter().eq(nodes.into_iter().map(|(_, _, id)| id)));
+}
+
+#[test]
+fn deep_chain_schedule() {
+    const CHAIN_LENGTH: usize = 4096;
+
+    let mut graph = AudioGraph::default();
+
+    let nodes: Vec<_> = iter::repeat_with(|| {
+        let mut node = Node {
+            latency: 1,
+            ..Default::default()
+        };
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    })
+    .take(CHAIN_LENGTH)
+    .collect();
+
+    // the signal flows towards lower IDs, so that the first node is fed by every other one
+    for pair in nodes.windows(2) {
+        let [(input_id, _, to_id), (_, output_id, from_id)] = pair else {
+            unreachable!()
+        };
+
+        assert!(graph
+            .try_insert_edge(
+                (from_id.clone(), output_id.clone()),
+                (to_id.clone(), input_id.clone()),
+            )
+            .is_ok_and(id));
+    }
+
+    let flow_order: Vec<_> = nodes.into_iter().rev().map(|(_, _, id)| id).collect();
+    let (first_id, last_id) = (&flow_order[0], &flow_order[CHAIN_LENGTH - 1]);
+
+    let (_, schedule) = graph.compile([last_id.clone()]);
+    let processed = schedule.iter().filter_map(|task| match task {
+        Task::Node { id, .. } => Some(id),
+        _ => None,
+    });
+    assert!(processed.eq(&flow_order));
+
+    assert_eq!(graph.topological_order().as_ref(), Some(&flow_order));
+    assert_eq!(
+        graph.path_latency(first_id, last_id),
+        Some(CHAIN_LENGTH as u64 - 1)
+    );
+    assert_eq!(
+        graph.critical_path(),
+        Some((flow_order, CHAIN_LENGTH as u64))
+    );
+}
+
+#[test]
+fn cycle_detection_matches_recursive() {
+    fn is_connected_recursive(graph: &AudioGraph, from: &NodeID, to: &NodeID) -> bool {
+        from == to
+            || graph[from]
+                .inputs()
+                .values()
+                .flat_map(|input| input.connections().keys())
+                .any(|node| is_connected_recursive(graph, node, to))
+    }
+
+    const NUM_NODES: usize = 8;
+
+    // xorshift, good enough for generating test graphs
+    let mut state = 0x2545_f491_u32;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as usize
+    };
+
+    for _ in 0..64 {
+        let mut graph = AudioGraph::default();
+
+        let nodes: [_; NUM_NODES] = array::from_fn(|_| {
+            let mut node = Node::default();
+            (node.add_input(), node.add_output(), graph.insert_node(node))
+        });
+
+        for _ in 0..2 * NUM_NODES {
+            let (_, output_id, from_id) = nodes[random() % NUM_NODES].clone();
+            let (input_id, _, to_id) = nodes[random() % NUM_NODES].clone();
+
+            let expected_cycle = is_connected_recursive(&graph, &from_id, &to_id);
+
+            match graph.insert_edge((from_id.clone(), output_id), (to_id.clone(), input_id)) {
+                Err(EdgeInsertError::CycleFound(CycleFound { path })) => {
+                    assert!(expected_cycle);
+                    assert_eq!(path.first(), Some(&to_id));
+                    assert_eq!(path.last(), Some(&from_id));
+
+                    for pair in path.windows(2) {
+                        assert!(graph.predecessors(&pair[1]).any(|node| node == &pair[0]));
+                    }
+                }
+                result => assert!(!expected_cycle && result.is_ok()),
+            }
+        }
+    }
+}
+
+#[test]
+fn aliasing() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut source = Node::default();
+    let source_output_id = source.add_output();
+    let source_id = graph.insert_node(source);
+
+    let mut node = Node::default();
+    let node_input_id = node.add_input();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    assert!(graph
+        .insert_edges(&[
+            (
+                (source_id, source_output_id),
+                (node_id.clone(), node_input_id.clone()),
+            ),
+            (
+                (node_id.clone(), node_output_id.clone()),
+                (master_id.clone(), master_input_id),
+            ),
+        ])
+        .is_ok());
+
+    let (_, schedule) = graph.compile([master_id]);
+
+    let node_task = schedule
+        .iter()
+        .find(|task| matches!(task, Task::Node { id, .. } if id == &node_id))
+        .unwrap();
+
+    assert!(node_task.aliases(&node_input_id, &node_output_id));
+    assert!(!node_task.aliases(&node_input_id, &OutputID(1)));
+    assert!(!Task::sum(0, 0, 0).aliases(&node_input_id, &node_output_id));
+}
+
+#[test]
+fn unconnected_ports() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 3] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    node.add_input();
+    let node_output_ids: [_; 2] = array::from_fn(|_| node.add_output());
+    node.add_output();
+    let node_id = graph.insert_node(node);
+
+    let [master_input1, master_input2, _] = master_input_ids;
+
+    for (output_id, input_id) in zip(node_output_ids.clone(), [master_input1, master_input2]) {
+        assert!(graph
+            .try_insert_edge((node_id.clone(), output_id), (master_id.clone(), input_id))
+            .is_ok_and(id));
+    }
+
+    let (num_buffers, schedule) = graph.compile([master_id]);
+
+    let [Task::Node {
+        id: first_id,
+        inputs: node_inputs,
+        outputs: node_outputs,
+    }, Task::Node {
+        inputs: master_inputs,
+        outputs: master_outputs,
+        ..
+    }] = schedule.as_slice()
+    else {
+        panic!("unexpected schedule: {schedule:#?}")
+    };
+
+    assert_eq!(first_id, &node_id);
+    assert!(node_inputs.is_empty());
+    assert!(master_outputs.is_empty());
+
+    // both outputs are written at once, so they can't share a buffer
+    let [output_buf1, output_buf2] = node_output_ids.each_ref().map(|id| node_outputs[id]);
+    assert_ne!(output_buf1, output_buf2);
+
+    let mut master_buffers: Vec<_> = master_inputs.values().copied().collect();
+    master_buffers.sort_unstable();
+    assert_eq!(master_buffers, [0, 1]);
+
+    assert_eq!(num_buffers, 2);
+}
+
+#[test]
+fn path_latency() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node {
+        latency: 1000,
+        ..Default::default()
+    };
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut source = Node {
+        latency: 16,
+        ..Default::default()
+    };
+    let source_output_id = source.add_output();
+    let source_id = graph.insert_node(source);
+
+    let [short_path, long_path] = [0, 64].map(|latency| {
+        let mut node = Node {
+            latency,
+            ..Default::default()
+        };
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    });
+
+    for (input_id, output_id, node_id) in [short_path, long_path.clone()] {
+        assert!(graph
+            .insert_edges(&[
+                (
+                    (source_id.clone(), source_output_id.clone()),
+                    (node_id.clone(), input_id),
+                ),
+                (
+                    (node_id, output_id),
+                    (master_id.clone(), master_input_id.clone()),
+                ),
+            ])
+            .is_ok());
+    }
+
+    assert_eq!(graph.path_latency(&source_id, &master_id), Some(80));
+    assert_eq!(graph.path_latency(&long_path.2, &master_id), Some(64));
+    assert_eq!(graph.path_latency(&master_id, &master_id), Some(0));
+    assert_eq!(graph.path_latency(&master_id, &source_id), None);
+
+    // bypassed nodes have no latency
+    graph.get_node_mut(&long_path.2).unwrap().bypassed = true;
+
+    assert_eq!(graph.path_latency(&source_id, &master_id), Some(16));
+    assert_eq!(graph.path_latency(&long_path.2, &master_id), Some(0));
+}
+
+#[test]
+fn latency_compensation() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut source = Node::default();
+    let source_output_id = source.add_output();
+    let source_id = graph.insert_node(source);
+
+    let [(short_input_id, short_output_id, short_id), (long_input_id, long_output_id, long_id)] =
+        [0, 64].map(|latency| {
+            let mut node = Node {
+                latency,
+                ..Default::default()
+            };
+            (node.add_input(), node.add_output(), graph.insert_node(node))
+        });
+
+    let short_edge = (
+        (short_id.clone(), short_output_id.clone()),
+        (master_id.clone(), master_input_id.clone()),
+    );
+
+    assert!(graph
+        .insert_edges(&[
+            (
+                (source_id.clone(), source_output_id.clone()),
+                (short_id.clone(), short_input_id.clone()),
+            ),
+            (
+                (source_id.clone(), source_output_id.clone()),
+                (long_id.clone(), long_input_id.clone()),
+            ),
+            short_edge.clone(),
+            (
+                (long_id.clone(), long_output_id.clone()),
+                (master_id.clone(), master_input_id.clone()),
+            ),
+        ])
+        .is_ok());
+
+    let (num_buffers, schedule) = graph.compile([master_id.clone()]);
+
+    // println!("{schedule:#?}");
+
+    assert_eq!(
+        schedule,
+        [
+            Task::node(source_id, [], [(source_output_id, 0)]),
+            Task::node(long_id, [(long_input_id, 0)], [(long_output_id, 1)]),
+            Task::node(short_id, [(short_input_id, 0)], [(short_output_id, 0)]),
+            Task::delay(short_edge, 0, 2, 64),
+            Task::sum(2, 1, 1),
+            Task::node(master_id, [(master_input_id, 1)], []),
+        ]
+    );
+
+    assert_eq!(num_buffers, 3);
+}
+
+#[test]
+fn bypass() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut source = Node::default();
+    let source_output_id = source.add_output();
+    let source_id = graph.insert_node(source);
+
+    // only the first input (by rank) is connected, so the second output is silenced
+    let mut effect = Node {
+        latency: 64,
+        bypassed: true,
+        ..Default::default()
+    };
+    let effect_input_id = effect.add_input();
+    effect.add_input();
+    let effect_output_ids: [_; 2] = array::from_fn(|_| effect.add_output());
+    let effect_id = graph.insert_node(effect);
+
+    assert!(graph
+        .try_insert_edge(
+            (source_id.clone(), source_output_id.clone()),
+            (effect_id.clone(), effect_input_id),
+        )
+        .is_ok_and(id));
+
+    for (output_id, input_id) in zip(effect_output_ids, master_input_ids.clone()) {
+        assert!(graph
+            .try_insert_edge(
+                (effect_id.clone(), output_id),
+                (master_id.clone(), input_id)
+            )
+            .is_ok_and(id));
+    }
+
+    let (num_buffers, schedule) = graph.compile([master_id.clone()]);
+
+    // println!("{schedule:#?}");
+
+    let [master_input1, master_input2] = master_input_ids;
+
+    assert_eq!(
+        schedule,
+        [
+            Task::node(source_id, [], [(source_output_id, 0)]),
+            Task::copy(0, 2),
+            Task::clear(1),
+            Task::node(master_id, [(master_input1, 2), (master_input2, 1)], []),
+        ]
+    );
+
+    assert_eq!(num_buffers, 3);
+}
+
+#[test]
+fn skip_unneeded_nodes() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let [(node_output_id, node_id), (tail_output_id, tail_id)] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_output(), graph.insert_node(node))
+    });
+
+    let mut tail_sink = Node::default();
+    let tail_sink_input_id = tail_sink.add_input();
+    let tail_sink_id = graph.insert_node(tail_sink);
+
+    assert!(graph
+        .insert_edges(&[
+            (
+                (node_id.clone(), node_output_id.clone()),
+                (master_id.clone(), master_input_id.clone()),
+            ),
+            (
+                (tail_id, tail_output_id),
+                (tail_sink_id, tail_sink_input_id)
+            ),
+        ])
+        .is_ok());
+
+    let (num_buffers, schedule) = graph.compile([master_id.clone()]);
+
+    assert_eq!(
+        schedule,
+        [
+            Task::node(node_id, [], [(node_output_id, 0)]),
+            Task::node(master_id, [(master_input_id, 0)], []),
+        ]
+    );
+
+    assert_eq!(num_buffers, 1);
+}
+
+#[test]
+fn replace_node() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut source = Node::default();
+    let source_output_id = source.add_output();
+    let source_id = graph.insert_node(source);
+
+    let mut node = Node::default();
+    let node_input_ids: [_; 2] = array::from_fn(|_| node.add_input());
+    let node_output_ids: [_; 2] = array::from_fn(|_| node.add_output());
+    let node_id = graph.insert_node(node);
+
+    for input_id in node_input_ids.clone() {
+        assert!(graph
+            .try_insert_edge(
+                (source_id.clone(), source_output_id.clone()),
+                (node_id.clone(), input_id),
+            )
+            .is_ok_and(id));
+    }
+
+    for (output_id, input_id) in zip(node_output_ids.clone(), master_input_ids.clone()) {
+        assert!(graph
+            .try_insert_edge((node_id.clone(), output_id), (master_id.clone(), input_id))
+            .is_ok_and(id));
+    }
+
+    // only has the first input and output of the node it replaces
+    let mut replacement = Node::default();
+    replacement.add_input();
+    replacement.add_output();
+
+    let replaced = graph.replace_node(&node_id, replacement).unwrap();
+
+    assert_eq!(replaced.inputs().len(), 2);
+    assert!(replaced
+        .inputs()
+        .values()
+        .all(|input| input.connections().is_empty()));
+
+    let [node_input_id, _] = node_input_ids;
+    let [node_output_id, _] = node_output_ids;
+    let [master_input_id, _] = master_input_ids;
+
+    let mut edges: Vec<_> = graph.edges().collect();
+    edges.sort_unstable();
+
+    assert_eq!(
+        edges,
+        [
+            (
+                (source_id, source_output_id),
+                (node_id.clone(), node_input_id),
+            ),
+            (
+                (node_id.clone(), node_output_id),
+                (master_id, master_input_id),
+            ),
+        ]
+    );
+
+    assert!(graph.replace_node(&NodeID(42), Node::default()).is_err());
+}
+
+#[test]
+fn metadata() {
+    let mut graph = AudioGraph::new();
+
+    let node_id = graph.insert_node(Node::default());
+    assert!(graph.get_metadata(&node_id).is_none());
+
+    assert_eq!(graph.set_metadata(&node_id, "reverb"), None);
+    assert_eq!(graph.set_metadata(&node_id, "delay"), Some("reverb"));
+    assert_eq!(graph.get_metadata(&node_id), Some(&"delay"));
+
+    let (subgraph, new_ids) = graph.extract_subgraph(slice::from_ref(&node_id));
+    assert_eq!(subgraph.get_metadata(&new_ids[&node_id]), Some(&"delay"));
+
+    assert!(graph.remove_node(&node_id).is_some());
+
+    // the slot is reused, but not the metadata
+    assert_eq!(graph.insert_node(Node::default()), node_id);
+    assert!(graph.get_metadata(&node_id).is_none());
+}
+
+#[test]
+fn parallel_edges() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    let other_id = graph.insert_node(Node::default());
+
+    let mut expected = master_input_ids.map(|input_id| {
+        (
+            (node_id.clone(), node_output_id.clone()),
+            (master_id.clone(), input_id),
+        )
+    });
+
+    assert!(graph.insert_edges(&expected).is_ok());
+
+    let mut edges = graph.parallel_edges(&master_id, &node_id);
+
+    edges.sort_unstable();
+    expected.sort_unstable();
+
+    assert_eq!(edges, expected);
+    assert!(graph.parallel_edges(&node_id, &other_id).is_empty());
+}
+
+#[test]
+fn structural_hash() {
+    let build = |reversed: bool| {
+        let mut graph = AudioGraph::default();
+
+        let mut master = Node::default();
+        let master_input_id = master.add_input();
+        let master_id = graph.insert_node(master);
+
+        let mut edges: Vec<_> = iter::repeat_with(|| {
+            let mut node = Node::default();
+            let output_id = node.add_output();
+            (
+                (graph.insert_node(node), output_id),
+                (master_id.clone(), master_input_id.clone()),
+            )
+        })
+        .take(8)
+        .collect();
+
+        if reversed {
+            edges.reverse();
+        }
+
+        assert!(graph.insert_edges(&edges).is_ok());
+
+        graph
+    };
+
+    let graph = build(false);
+    let mut other = build(true);
+
+    assert_eq!(graph, other);
+    assert_eq!(graph.structural_hash(), other.structural_hash());
+
+    other.insert_node(Node::default());
+
+    assert_ne!(graph, other);
+    assert_ne!(graph.structural_hash(), other.structural_hash());
+}
+
+#[test]
+fn clear_connections() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    let edges = master_input_ids.map(|input_id| {
+        (
+            (node_id.clone(), node_output_id.clone()),
+            (master_id.clone(), input_id),
+        )
+    });
+
+    assert!(graph.insert_edges(&edges).is_ok());
+
+    graph.clear_connections();
+
+    assert_eq!(graph.num_nodes(), 2);
+    assert_eq!(graph[&master_id].inputs().len(), 2);
+    assert_eq!(graph[&node_id].output_ids().len(), 1);
+    assert_eq!(graph.edges().count(), 0);
+}
+
+#[test]
+fn disconnect_ports() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_ids: [_; 2] = array::from_fn(|_| node.add_output());
+    let node_id = graph.insert_node(node);
+
+    for output_id in &node_output_ids {
+        for input_id in &master_input_ids {
+            assert!(graph
+                .insert_edge(
+                    (node_id.clone(), output_id.clone()),
+                    (master_id.clone(), input_id.clone()),
+                )
+                .is_ok_and(id));
+        }
+    }
+
+    let [input_id, _] = &master_input_ids;
+    let mut outputs = graph.disconnect_input(&master_id, input_id);
+    outputs.sort_unstable();
+
+    assert_eq!(
+        outputs,
+        node_output_ids.clone().map(|id| (node_id.clone(), id))
+    );
+    assert_eq!(graph.edges().count(), 2);
+
+    let [output_id, _] = &node_output_ids;
+    let inputs = graph.disconnect_output(&node_id, output_id);
+
+    assert_eq!(inputs, [(master_id.clone(), master_input_ids[1].clone())]);
+    assert_eq!(graph.edges().count(), 1);
+
+    assert!(graph.disconnect_input(&master_id, input_id).is_empty());
+    assert!(graph.disconnect_output(&node_id, output_id).is_empty());
+}
+
+#[test]
+fn undo_commands() {
+    let mut graph = AudioGraph::<u32>::new();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node.clone());
+    graph.set_metadata(&node_id, 42);
+
+    let edge = (
+        (node_id.clone(), node_output_id.clone()),
+        (master_id.clone(), master_input_id.clone()),
+    );
+
+    let original = graph.clone();
+
+    let commands = [
+        GraphCommand::InsertEdge(edge.clone()),
+        GraphCommand::InsertNode {
+            id: None,
+            node,
+            metadata: Some(7),
+            edges: vec![(
+                (NodeID(2), node_output_id),
+                (master_id.clone(), master_input_id),
+            )],
+        },
+        GraphCommand::RemoveNode(node_id.clone()),
+    ];
+
+    let mut undo_stack = vec![];
+
+    for command in commands {
+        undo_stack.push(graph.apply(command).unwrap());
+    }
+
+    assert_eq!(graph.num_nodes(), 2);
+    assert_eq!(graph.get_metadata(&NodeID(2)), Some(&7));
+    assert_eq!(
+        graph.apply(GraphCommand::RemoveEdge(edge.clone())),
+        Err(CommandError::EdgeNotFound),
+    );
+
+    let undo_node = undo_stack.last().unwrap().clone();
+
+    while let Some(command) = undo_stack.pop() {
+        graph.apply(command).unwrap();
+    }
+
+    assert_eq!(graph, original);
+    assert_eq!(graph.apply(undo_node), Err(CommandError::NodeOccupied),);
+    assert_eq!(graph, original);
+
+    // the edge doesn't start or end at the inserted node
+    assert_eq!(
+        graph.apply(GraphCommand::InsertNode {
+            id: None,
+            node: Node::default(),
+            metadata: None,
+            edges: vec![edge],
+        }),
+        Err(CommandError::EdgeNotConnected),
+    );
+    assert_eq!(graph, original);
+    assert_eq!(graph.num_nodes(), 2);
+}
+
+#[test]
+fn id_reuse() {
+    let mut graph = AudioGraph::default();
+
+    let ids: [_; 4] = array::from_fn(|_| graph.insert_node(Node::default()));
+
+    assert_eq!(graph.num_holes(), 0);
+    assert_eq!(graph.next_node_id(), NodeID(4));
+
+    graph.remove_node(&ids[2]);
+    graph.remove_node(&ids[1]);
+
+    assert_eq!(graph.num_holes(), 2);
+    assert_eq!(graph.next_node_id(), ids[1]);
+    assert_eq!(graph.insert_node(Node::default()), ids[1]);
+    assert_eq!(graph.num_holes(), 1);
+
+    graph.remove_node(&ids[3]);
+
+    assert_eq!(graph.num_holes(), 0);
+    assert_eq!(graph.next_node_id(), ids[2]);
+}
+
+#[test]
+fn compact() {
+    let mut graph = AudioGraph::<u32>::new();
+
+    let ids: [_; 5] = array::from_fn(|i| {
+        let mut node = Node::default();
+        let ports = (node.add_input(), node.add_output());
+        let id = graph.insert_node(node);
+        graph.set_metadata(&id, i as u32);
+        (id, ports)
+    });
+
+    for [(from, (_, output_id)), (to, (input_id, _))] in [[&ids[0], &ids[2]], [&ids[2], &ids[4]]] {
+        assert!(graph
+            .insert_edge(
+                (from.clone(), output_id.clone()),
+                (to.clone(), input_id.clone())
+            )
+            .is_ok_and(id));
+    }
+
+    graph.remove_node(&ids[1].0);
+    graph.remove_node(&ids[3].0);
+
+    let new_ids = graph.compact();
+
+    assert_eq!(graph.num_holes(), 0);
+    assert_eq!(
+        new_ids,
+        FnvHashMap::from_iter([
+            (NodeID(0), NodeID(0)),
+            (NodeID(2), NodeID(1)),
+            (NodeID(4), NodeID(2)),
+        ]),
+    );
+    assert_eq!(graph.get_metadata(&NodeID(1)), Some(&2));
+    assert_eq!(graph.get_metadata(&NodeID(2)), Some(&4));
+
+    let mut edges: Vec<_> = graph.edges().collect();
+    edges.sort_unstable();
+
+    assert_eq!(
+        edges,
+        [
+            ((NodeID(0), OutputID(0)), (NodeID(1), InputID(0))),
+            ((NodeID(1), OutputID(0)), (NodeID(2), InputID(0))),
+        ],
+    );
+
+    // bypass `insert_edge` to connect a missing node
+    graph
+        .get_node_mut(&NodeID(2))
+        .unwrap()
+        .get_input_mut(&InputID(0))
+        .unwrap()
+        .insert_output((NodeID(7), OutputID(0)));
+    assert!(graph.validate().is_err());
+
+    graph.compact();
+
+    assert!(graph.validate().is_ok());
+    assert_eq!(graph.edges().count(), 2);
+}
+
+#[test]
+fn snapshot() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<GraphSnapshot>();
+
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node {
+        bypassed: true,
+        ..Default::default()
+    };
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node {
+        latency: 64,
+        ..Default::default()
+    };
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    let edge = (
+        (node_id.clone(), node_output_id.clone()),
+        (master_id.clone(), master_input_ids[1].clone()),
+    );
+
+    assert!(graph
+        .insert_edge(edge.0.clone(), edge.1.clone())
+        .is_ok_and(id));
+
+    let snapshot = graph.snapshot();
+
+    graph.clear_connections();
+
+    assert_eq!(
+        snapshot,
+        GraphSnapshot {
+            nodes: vec![
+                NodeSnapshot {
+                    id: master_id,
+                    latency: 0,
+                    bypassed: true,
+                    input_ids: master_input_ids.to_vec(),
+                    output_ids: vec![],
+                },
+                NodeSnapshot {
+                    id: node_id,
+                    latency: 64,
+                    bypassed: false,
+                    input_ids: vec![],
+                    output_ids: vec![node_output_id],
+                },
+            ],
+            edges: vec![edge],
+        },
+    );
+}
+
+#[test]
+fn max_fan_in() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    master
+        .get_input_mut(&master_input_id)
+        .unwrap()
+        .set_max_fan_in(Some(1));
+    let master_id = graph.insert_node(master);
+
+    let [first, second] = array::from_fn(|_| {
+        let mut node = Node::default();
+        let output_id = node.add_output();
+        (graph.insert_node(node), output_id)
+    });
+
+    let to = (master_id.clone(), master_input_id.clone());
+
+    assert!(graph.insert_edge(first.clone(), to.clone()).is_ok_and(id));
+    assert!(graph
+        .insert_edge(first.clone(), to.clone())
+        .is_ok_and(|inserted| !inserted));
+    assert_eq!(
+        graph.insert_edge(second.clone(), to.clone()),
+        Err(EdgeInsertError::FanInExceeded),
+    );
+
+    graph
+        .get_node_mut(&master_id)
+        .unwrap()
+        .get_input_mut(&master_input_id)
+        .unwrap()
+        .set_max_fan_in(None);
+
+    assert!(graph.insert_edge(second, to).is_ok_and(id));
+    assert_eq!(graph[&master_id].inputs()[&master_input_id].fan_in(), 2);
+}
+
+#[test]
+fn error_messages() {
+    let cycle = CycleFound {
+        path: vec![NodeID(1), NodeID(2), NodeID(3)],
+    };
+
+    assert_eq!(
+        cycle.to_string(),
+        "edge would create a cycle through nodes 1 -> 2 -> 3",
+    );
+    assert_eq!(
+        EdgeInsertError::CycleFound(cycle).to_string(),
+        "edge would create a cycle through nodes 1 -> 2 -> 3",
+    );
+
+    let edge = ((NodeID(4), OutputID(1)), (NodeID(0), InputID(2)));
+
+    assert_eq!(
+        PortError::OutputNotFound(edge).to_string(),
+        "edge from output 1 of node 4 to input 2 of node 0: source node has no such output",
+    );
+}
+
+#[test]
+fn deterministic_schedule() {
+    let build = || {
+        let mut graph = AudioGraph::default();
+
+        let mut master = Node::default();
+        let master_input_id = master.add_input();
+        let master_id = graph.insert_node(master);
+
+        for _ in 0..16 {
+            let mut node = Node::default();
+            let output_id = node.add_output();
+            let node_id = graph.insert_node(node);
+
+            assert!(graph
+                .insert_edge(
+                    (node_id, output_id),
+                    (master_id.clone(), master_input_id.clone())
+                )
+                .is_ok_and(id));
+        }
+
+        (graph, master_id)
+    };
+
+    let (graph, master_id) = build();
+    let (other, _) = build();
+
+    let compiled = graph.compile([master_id.clone()]);
+
+    assert_eq!(compiled, other.compile([master_id.clone()]));
+    assert_eq!(compiled, graph.clone().compile([master_id]));
+}
+
+#[test]
+fn move_edge() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_ids: [_; 2] = array::from_fn(|_| master.add_input());
+    master
+        .get_input_mut(&master_input_ids[1])
+        .unwrap()
+        .set_max_fan_in(Some(1));
+    let master_id = graph.insert_node(master);
+
+    let [(a_output_id, a_id), (b_output_id, b_id)] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_output(), graph.insert_node(node))
+    });
+
+    let [old_to, new_to] = master_input_ids.map(|input_id| (master_id.clone(), input_id));
+    let from = (a_id.clone(), a_output_id.clone());
+
+    assert!(graph
+        .insert_edge(from.clone(), old_to.clone())
+        .is_ok_and(id));
+
+    assert!(graph
+        .move_edge(from.clone(), old_to.clone(), new_to.clone())
+        .is_ok_and(id));
+    assert_eq!(
+        graph.edges().collect::<Vec<_>>(),
+        [(from.clone(), new_to.clone())]
+    );
+
+    // moving an edge that doesn't exist does nothing
+    assert!(graph
+        .move_edge(from.clone(), old_to.clone(), new_to.clone())
+        .is_ok_and(|moved| !moved));
+
+    let other_from = (b_id, b_output_id);
+    assert!(graph
+        .insert_edge(other_from.clone(), old_to.clone())
+        .is_ok_and(id));
+
+    // the new input is full, the old edge must be kept
+    assert_eq!(
+        graph.move_edge(other_from.clone(), old_to.clone(), new_to),
+        Err(EdgeInsertError::FanInExceeded),
+    );
+    assert_eq!(graph.edges().count(), 2);
+    assert!(graph[&master_id].inputs()[&old_to.1]
+        .connections()
+        .contains_key(&other_from.0));
+}
+
+#[test]
+fn find_any_cycle() {
+    let mut graph = AudioGraph::default();
+
+    let nodes: [_; 4] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    });
+
+    for [(_, output_id, from), (input_id, _, to)] in
+        [[0, 1], [1, 2], [2, 3]].map(|p| p.map(|i| &nodes[i]))
+    {
+        assert!(graph
+            .insert_edge(
+                (from.clone(), output_id.clone()),
+                (to.clone(), input_id.clone())
+            )
+            .is_ok_and(id));
+    }
+
+    assert!(graph.is_acyclic());
+
+    // bypass `insert_edge`, like a graph loaded from elsewhere would
+    let [_, (input_id, _, node1), _, (_, output_id, node3)] = &nodes;
+    graph
+        .get_node_mut(node1)
+        .unwrap()
+        .get_input_mut(input_id)
+        .unwrap()
+        .insert_output((node3.clone(), output_id.clone()));
+
+    assert!(!graph.is_acyclic());
+    assert_eq!(graph.topological_order(), None);
+
+    let mut cycle = graph.find_any_cycle().unwrap();
+
+    // the path may start anywhere on the cycle
+    let start = cycle.iter().position(|node| node == node1).unwrap();
+    cycle.rotate_left(start);
+
+    assert_eq!(cycle, [node1.clone(), nodes[2].2.clone(), node3.clone()]);
+}
+
+#[test]
+fn port_kinds() {
+    const CONTROL: PortKind = PortKind(1);
+
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let [audio_input_id, control_input_id] = array::from_fn(|_| master.add_input());
+    master
+        .get_input_mut(&control_input_id)
+        .unwrap()
+        .set_kind(CONTROL);
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let [audio_output_id, control_output_id] = array::from_fn(|_| node.add_output());
+    assert!(node.set_output_kind(&control_output_id, CONTROL));
+    assert!(!node.set_output_kind(&OutputID(42), CONTROL));
+    let node_id = graph.insert_node(node);
+
+    assert_eq!(
+        graph[&node_id].output_kind(&audio_output_id),
+        Some(PortKind::default())
+    );
+    assert_eq!(graph[&node_id].output_kind(&OutputID(42)), None);
+
+    let audio_output = (node_id.clone(), audio_output_id);
+    let control_output = (node_id.clone(), control_output_id);
+    let audio_input = (master_id.clone(), audio_input_id);
+    let control_input = (master_id.clone(), control_input_id);
+
+    assert_eq!(
+        graph.insert_edge(audio_output.clone(), control_input.clone()),
+        Err(EdgeInsertError::TypeMismatch),
+    );
+    assert_eq!(
+        graph.insert_edge(control_output.clone(), audio_input.clone()),
+        Err(EdgeInsertError::TypeMismatch),
+    );
+    assert!(graph.insert_edge(audio_output, audio_input).is_ok_and(id));
+    assert!(graph
+        .insert_edge(control_output, control_input)
+        .is_ok_and(id));
+}
+
+#[test]
+fn diff() {
+    let mut old = AudioGraph::<u32>::new();
+
+    let nodes: [_; 4] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), old.insert_node(node))
+    });
+
+    let edge = |from: usize, to: usize| {
+        let (_, output_id, from) = &nodes[from];
+        let (input_id, _, to) = &nodes[to];
+        (
+            (from.clone(), output_id.clone()),
+            (to.clone(), input_id.clone()),
+        )
+    };
+
+    assert!(old
+        .insert_edges(&[edge(0, 1), edge(1, 2), edge(2, 3)])
+        .is_ok());
+    old.set_metadata(&nodes[0].2, 1);
+
+    let mut new = old.clone();
+
+    // remove an edge, leaving it's nodes in place
+    assert!(new.apply(GraphCommand::RemoveEdge(edge(2, 3))).is_ok());
+    // change a node's settings, it's edges must be kept
+    new.get_node_mut(&nodes[1].2).unwrap().latency = 64;
+    // add a node, connected to an existing one
+    let mut node = Node::default();
+    let output_id = node.add_output();
+    let new_id = new.insert_node(node);
+    assert!(new
+        .insert_edge(
+            (new_id, output_id),
+            (nodes[3].2.clone(), nodes[3].0.clone())
+        )
+        .is_ok_and(id));
+    // update metadata
+    new.set_metadata(&nodes[0].2, 2);
+    new.set_metadata(&nodes[2].2, 3);
+
+    let commands = old.diff(&new);
+
+    assert!(matches!(commands[0], GraphCommand::RemoveEdge(_)));
+    assert!(matches!(
+        &commands[1],
+        GraphCommand::ReplaceNode { id, node, edges, removed_edges }
+            if id == &nodes[1].2 && node.latency == 64 && edges.is_empty() && removed_edges.is_empty()
+    ));
+
+    for command in commands {
+        assert!(old.apply(command).is_ok());
+    }
+
+    assert_eq!(old, new);
+    assert!(old.diff(&new).is_empty());
+}
+
+#[test]
+fn diff_bypassed() {
+    let mut old = AudioGraph::<u32>::new();
+
+    let nodes: [_; 3] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), old.insert_node(node))
+    });
+
+    for i in 0..2 {
+        let (_, output_id, from) = &nodes[i];
+        let (input_id, _, to) = &nodes[i + 1];
+        assert!(old
+            .insert_edge(
+                (from.clone(), output_id.clone()),
+                (to.clone(), input_id.clone())
+            )
+            .is_ok_and(id));
+    }
+
+    old.set_metadata(&nodes[1].2, 1);
+
+    let mut new = old.clone();
+    new.get_node_mut(&nodes[1].2).unwrap().bypassed = true;
+
+    let commands = old.diff(&new);
+    assert_eq!(commands.len(), 1);
+
+    let mut graph = old.clone();
+    let undo = graph.apply(commands[0].clone()).unwrap();
+    assert_eq!(graph, new);
+
+    assert!(graph.apply(undo).is_ok());
+    assert_eq!(graph, old);
+}
+
+#[test]
+fn replace_node_command() {
+    let mut graph = AudioGraph::<()>::new();
+
+    let mut node = Node::default();
+    let input_ids: [_; 2] = array::from_fn(|_| node.add_input());
+    let to = graph.insert_node(node);
+
+    let mut node = Node::default();
+    let output_id = node.add_output();
+    let from = graph.insert_node(node);
+
+    let edges = input_ids
+        .clone()
+        .map(|input_id| ((from.clone(), output_id.clone()), (to.clone(), input_id)));
+    assert!(graph.insert_edges(&edges).is_ok());
+
+    let original = graph.clone();
+
+    // drop one of the inputs, along with it's edge
+    let mut node = graph[&to].without_connections();
+    node.remove_input(&input_ids[1]);
+
+    let undo = graph
+        .apply(GraphCommand::ReplaceNode {
+            id: to.clone(),
+            node,
+            edges: vec![],
+            removed_edges: vec![],
+        })
+        .unwrap();
+
+    assert_eq!(
+        undo,
+        GraphCommand::ReplaceNode {
+            id: to.clone(),
+            node: original[&to].without_connections(),
+            edges: vec![edges[1].clone()],
+            removed_edges: vec![],
+        }
+    );
+    assert!(graph.edges().eq([edges[0].clone()]));
+
+    assert!(graph.apply(undo).is_ok());
+    assert_eq!(graph, original);
+
+    // rewire the node through a new input
+    let mut node = graph[&to].without_connections();
+    node.remove_input(&input_ids[1]);
+    let new_input_id = node.add_input();
+    let new_edge = (
+        (from.clone(), output_id.clone()),
+        (to.clone(), new_input_id),
+    );
+
+    let undo = graph
+        .apply(GraphCommand::ReplaceNode {
+            id: to.clone(),
+            node,
+            edges: vec![new_edge.clone()],
+            removed_edges: vec![edges[0].clone()],
+        })
+        .unwrap();
+
+    assert!(graph.edges().eq([new_edge.clone()]));
+
+    let redo = graph.apply(undo).unwrap();
+    assert_eq!(graph, original);
+
+    assert!(graph.apply(redo).is_ok());
+    assert!(graph.edges().eq([new_edge]));
+
+    // removing a missing edge leaves the graph untouched
+    let before = graph.clone();
+    assert_eq!(
+        graph.apply(GraphCommand::ReplaceNode {
+            id: to.clone(),
+            node: Node::default(),
+            edges: vec![],
+            removed_edges: vec![edges[0].clone()],
+        }),
+        Err(CommandError::EdgeNotFound)
+    );
+    assert_eq!(graph, before);
+
+    assert_eq!(
+        graph.apply(GraphCommand::ReplaceNode {
+            id: NodeID(2),
+            node: Node::default(),
+            edges: vec![],
+            removed_edges: vec![],
+        }),
+        Err(CommandError::NodeNotFound)
+    );
+}
+
+#[test]
+fn shared_output() {
+    let mut graph = AudioGraph::default();
+
+    let mut producer = Node::default();
+    let producer_output_id = producer.add_output();
+    let producer_id = graph.insert_node(producer);
+
+    let [(a_input_id, a_id), (b_input_id, b_id)] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), graph.insert_node(node))
+    });
+
+    let from = (producer_id.clone(), producer_output_id.clone());
+
+    assert!(graph
+        .insert_edge(from.clone(), (a_id.clone(), a_input_id.clone()))
+        .is_ok_and(id));
+
+    assert!(!graph.is_shared_output(&producer_id, &producer_output_id));
+
+    assert!(graph
+        .insert_edge(from, (b_id.clone(), b_input_id.clone()))
+        .is_ok_and(id));
+
+    assert!(graph.is_shared_output(&producer_id, &producer_output_id));
+
+    let (num_buffers, schedule) = graph.compile([a_id.clone(), b_id.clone()]);
+
+    // println!("{schedule:#?}");
+
+    assert_eq!(num_buffers, 1);
+    assert_eq!(
+        schedule[0],
+        Task::node(producer_id, [], [(producer_output_id, 0)])
+    );
+    assert!(schedule[1..].contains(&Task::node(a_id, [(a_input_id, 0)], [])));
+    assert!(schedule[1..].contains(&Task::node(b_id, [(b_input_id, 0)], [])));
+}
+
+#[test]
+fn insertion_order() {
+    let mut graph = AudioGraph::default();
+
+    let ids: [_; 4] = array::from_fn(|_| graph.insert_node(Node::default()));
+
+    graph.remove_node(&ids[1]);
+    graph.remove_node(&ids[3]);
+
+    // reuses ids[1]
+    let new_id = graph.insert_node(Node::default());
+
+    assert_eq!(new_id, ids[1]);
+    assert_eq!(
+        graph.nodes_by_insertion_order(),
+        [ids[0].clone(), ids[2].clone(), new_id]
+    );
+
+    graph.compact();
+
+    assert_eq!(
+        graph.nodes_by_insertion_order(),
+        [NodeID(0), NodeID(2), NodeID(1)]
+    );
+}
+
+#[test]
+fn critical_path() {
+    let mut graph = AudioGraph::default();
+
+    assert_eq!(graph.critical_path(), Some((vec![], 0)));
+
+    let [a, b, c, d] =
+        [(10, false), (20, true), (5, false), (100, false)].map(|(latency, bypassed)| {
+            let mut node = Node {
+                latency,
+                bypassed,
+                ..Default::default()
+            };
+            (node.add_input(), node.add_output(), graph.insert_node(node))
+        });
+
+    for [(_, output_id, from), (input_id, _, to)] in [[&a, &b], [&b, &c], [&d, &c]] {
+        assert!(graph
+            .insert_edge(
+                (from.clone(), output_id.clone()),
+                (to.clone(), input_id.clone())
+            )
+            .is_ok_and(id));
+    }
+
+    assert_eq!(
+        graph.critical_path(),
+        Some((vec![a.2.clone(), b.2.clone(), c.2.clone()], 15)),
+    );
+
+    // bypass `insert_edge` to create a cycle
+    graph
+        .get_node_mut(&a.2)
+        .unwrap()
+        .get_input_mut(&a.0)
+        .unwrap()
+        .insert_output((c.2, c.1));
+
+    assert_eq!(graph.critical_path(), None);
+}
+
+#[test]
+fn unsatisfied_inputs() {
+    let mut graph = AudioGraph::default();
+
+    let mut filter = Node::default();
+    let [audio_input_id, sidechain_input_id] = array::from_fn(|_| filter.add_input());
+    filter
+        .get_input_mut(&audio_input_id)
+        .unwrap()
+        .set_required(true);
+    let filter_id = graph.insert_node(filter);
+
+    let mut source = Node::default();
+    let source_output_id = source.add_output();
+    let source_id = graph.insert_node(source);
+
+    assert_eq!(
+        graph.unsatisfied_inputs(),
+        [(filter_id.clone(), audio_input_id.clone())]
+    );
+
+    assert!(graph
+        .insert_edge(
+            (source_id.clone(), source_output_id.clone()),
+            (filter_id.clone(), sidechain_input_id)
+        )
+        .is_ok_and(id));
+
+    assert_eq!(graph.unsatisfied_inputs().len(), 1);
+
+    assert!(graph
+        .insert_edge((source_id, source_output_id), (filter_id, audio_input_id))
+        .is_ok_and(id));
+
+    assert!(graph.unsatisfied_inputs().is_empty());
+}
+
+#[test]
+fn sources_and_sinks() {
+    let mut graph = AudioGraph::default();
+
+    let [a, b, c] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    });
+
+    assert!(graph
+        .insert_edge((a.2.clone(), a.1), (b.2.clone(), b.0))
+        .is_ok_and(id));
+
+    // c isn't connected to anything
+    assert_eq!(graph.sources(), [a.2.clone(), c.2.clone()]);
+    assert_eq!(graph.sinks(), [b.2, c.2]);
+}
+
+#[test]
+fn remove_ports() {
+    let mut graph = AudioGraph::default();
+
+    let mut mixer = Node::default();
+    let mixer_input_ids: [_; 2] = array::from_fn(|_| mixer.add_input());
+    let mixer_output_id = mixer.add_output();
+    let mixer_id = graph.insert_node(mixer);
+
+    let mut source = Node::default();
+    let source_output_id = source.add_output();
+    let source_id = graph.insert_node(source);
+
+    let mut sink = Node::default();
+    let sink_input_id = sink.add_input();
+    let sink_id = graph.insert_node(sink);
+
+    for input_id in &mixer_input_ids {
+        assert!(graph
+            .insert_edge(
+                (source_id.clone(), source_output_id.clone()),
+                (mixer_id.clone(), input_id.clone())
+            )
+            .is_ok_and(id));
+    }
+
+    assert!(graph
+        .insert_edge(
+            (mixer_id.clone(), mixer_output_id.clone()),
+            (sink_id.clone(), sink_input_id.clone())
+        )
+        .is_ok_and(id));
+
+    assert_eq!(
+        graph.remove_input(&mixer_id, &mixer_input_ids[1]),
+        Some(vec![(source_id.clone(), source_output_id.clone())]),
+    );
+    assert_eq!(graph.remove_input(&mixer_id, &mixer_input_ids[1]), None);
+
+    assert_eq!(
+        graph.remove_output(&mixer_id, &mixer_output_id),
+        Some(vec![(sink_id, sink_input_id)]),
+    );
+    assert_eq!(graph.remove_output(&mixer_id, &mixer_output_id), None);
+
+    assert_eq!(graph[&mixer_id].inputs().len(), 1);
+    assert!(graph[&mixer_id].output_ids().is_empty());
+    assert_eq!(graph.edges().count(), 1);
+    assert!(graph.validate().is_ok());
+}
+
+#[test]
+fn edges_on_ports_above() {
+    let mut graph = AudioGraph::default();
+
+    let mut mixer = Node::default();
+    let mixer_input_ids: [_; 3] = array::from_fn(|_| mixer.add_input());
+    let mixer_output_ids: [_; 2] = array::from_fn(|_| mixer.add_output());
+    let mixer_id = graph.insert_node(mixer);
+
+    let mut node = Node::default();
+    let node_input_id = node.add_input();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    let inputs = mixer_input_ids.map(|input_id| {
+        (
+            (node_id.clone(), node_output_id.clone()),
+            (mixer_id.clone(), input_id),
+        )
+    });
+
+    let output = (
+        (mixer_id.clone(), mixer_output_ids[1].clone()),
+        (node_id.clone(), node_input_id),
+    );
+
+    // the node both feeds the mixer and is fed by it, so build a separate graph for each
+    let mut other = graph.clone();
+
+    assert!(graph.insert_edges(&inputs).is_ok());
+    assert!(other.insert_edges(slice::from_ref(&output)).is_ok());
+
+    assert_eq!(
+        graph.edges_on_ports_above(&mixer_id, 1),
+        inputs[1..].to_vec()
+    );
+    assert!(graph.edges_on_ports_above(&mixer_id, 3).is_empty());
+    assert_eq!(other.edges_on_ports_above(&mixer_id, 1), [output]);
+}
+
+#[test]
+fn unreachable_from() {
+    let mut graph = AudioGraph::default();
+
+    let [master, a, b, c, d] = array::from_fn(|_| {
+        let mut node = Node::default();
+        (node.add_input(), node.add_output(), graph.insert_node(node))
+    });
+
+    // a -> master, and c -> b, which feeds nothing, d is isolated
+    for [(_, output_id, from), (input_id, _, to)] in [[&a, &master], [&c, &b]] {
+        assert!(graph
+            .insert_edge(
+                (from.clone(), output_id.clone()),
+                (to.clone(), input_id.clone())
+            )
+            .is_ok_and(id));
+    }
+
+    assert_eq!(
+        graph.unreachable_from(slice::from_ref(&master.2)),
+        [b.2.clone(), c.2.clone(), d.2.clone()],
+    );
+    assert_eq!(graph.unreachable_from(&[master.2, b.2]), [d.2]);
+}
+
+#[test]
+fn clone_structure() {
+    let mut graph = AudioGraph::<u32>::new();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+    graph.set_metadata(&master_id, 1);
+
+    let mut node = Node {
+        latency: 64,
+        ..Default::default()
+    };
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    assert!(graph
+        .insert_edge(
+            (node_id, node_output_id),
+            (master_id.clone(), master_input_id)
+        )
+        .is_ok_and(id));
+
+    let structure = graph.clone_structure();
+
+    assert_eq!(structure.edges().count(), 0);
+    assert_eq!(structure.get_metadata(&master_id), Some(&1));
+
+    graph.clear_connections();
+
+    assert_eq!(structure, graph);
+}
+
+#[test]
+fn num_nodes() {
+    let mut graph = AudioGraph::default();
+
+    assert!(graph.is_empty());
+
+    let ids: [_; 3] = array::from_fn(|_| graph.insert_node(Node::default()));
+
+    assert_eq!(graph.num_nodes(), 3);
+
+    graph.remove_node(&ids[1]);
+
+    assert_eq!(graph.num_nodes(), 2);
+    assert!(!graph.is_empty());
+
+    // removing a missing node changes nothing
+    graph.remove_node(&ids[1]);
+
+    assert_eq!(graph.num_nodes(), 2);
+
+    graph.remove_node(&ids[0]);
+    graph.remove_node(&ids[2]);
+
+    assert_eq!(graph.num_nodes(), 0);
+    assert!(graph.is_empty());
+}
+
+#[test]
+fn undo_past_lowered_fan_in() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let sources: [_; 2] = array::from_fn(|_| {
+        let mut node = Node::default();
+        let output_id = node.add_output();
+        (graph.insert_node(node), output_id)
+    });
+
+    for from in &sources {
+        assert!(graph
+            .insert_edge(from.clone(), (master_id.clone(), master_input_id.clone()))
+            .is_ok_and(id));
+    }
+
+    // existing edges are kept when the limit is lowered
+    graph
+        .get_node_mut(&master_id)
+        .unwrap()
+        .get_input_mut(&master_input_id)
+        .unwrap()
+        .set_max_fan_in(Some(1));
+
+    let original = graph.clone();
+
+    let undo_remove_node = graph
+        .apply(GraphCommand::RemoveNode(master_id.clone()))
+        .unwrap();
+    assert_eq!(
+        graph.clone().apply(undo_remove_node.clone()),
+        Err(CommandError::EdgeInsertError(
+            EdgeInsertError::FanInExceeded
+        ))
+    );
+    assert!(graph.restore(undo_remove_node).is_ok());
+    assert_eq!(graph, original);
+
+    let edge = (sources[0].clone(), (master_id, master_input_id));
+    let undo_remove_edge = graph.apply(GraphCommand::RemoveEdge(edge)).unwrap();
+    assert!(graph.restore(undo_remove_edge).is_ok());
+    assert_eq!(graph, original);
+
+    // so do diffs
+    let empty = original.clone_structure();
+    let mut synced = empty.clone();
+
+    for command in empty.diff(&original) {
+        assert!(synced.restore(command).is_ok());
+    }
+
+    assert_eq!(synced, original);
+}
+
+#[test]
+fn undo_past_changed_kinds() {
+    let mut graph = AudioGraph::default();
+
+    let mut master = Node::default();
+    let master_input_id = master.add_input();
+    let master_id = graph.insert_node(master);
+
+    let mut node = Node::default();
+    let node_output_id = node.add_output();
+    let node_id = graph.insert_node(node);
+
+    let edge = (
+        (node_id.clone(), node_output_id.clone()),
+        (master_id.clone(), master_input_id.clone()),
+    );
+
+    assert!(graph
+        .insert_edge(edge.0.clone(), edge.1.clone())
+        .is_ok_and(id));
+
+    // existing edges are kept when kinds change
+    graph
+        .get_node_mut(&master_id)
+        .unwrap()
+        .get_input_mut(&master_input_id)
+        .unwrap()
+        .set_kind(PortKind(1));
+    assert!(graph
+        .get_node_mut(&node_id)
+        .unwrap()
+        .set_output_kind(&node_output_id, PortKind(2)));
+
+    let original = graph.clone();
+
+    for id in [master_id, node_id] {
+        let undo = graph.apply(GraphCommand::RemoveNode(id)).unwrap();
+        assert!(graph.restore(undo).is_ok());
+        assert_eq!(graph, original);
+    }
+
+    let undo = graph.apply(GraphCommand::RemoveEdge(edge.clone())).unwrap();
+
+    // new edges are still checked
+    assert!(matches!(
+        graph.clone().apply(undo.clone()),
+        Err(CommandError::EdgeInsertError(EdgeInsertError::TypeMismatch))
+    ));
+
+    assert!(graph.restore(undo).is_ok());
+    assert_eq!(graph, original);
+}